@@ -1,22 +1,27 @@
 use std::fmt;
+use std::borrow::Cow;
+use std::future::Future;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::pin::Pin;
 use std::sync::{Arc, Weak, Mutex};
 use std::task::{Poll, Waker, Context};
 
-use futures_util::future::{ready, FutureExt};
+use futures_util::future::{ready, pending, FutureExt};
+use futures_channel::oneshot;
 use futures_signals::CancelableFutureHandle;
 use futures_signals::signal::{Signal, SignalExt, WaitFor, MutableSignal, Mutable};
 use futures_signals::signal_vec::{SignalVec, VecDiff};
-use discard::DiscardOnDrop;
+use discard::{Discard, DiscardOnDrop};
 use pin_project::pin_project;
-use wasm_bindgen::{JsCast, UnwrapThrowExt};
+use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
 use wasm_bindgen::closure::Closure;
 use web_sys::window;
 
+use crate::bindings;
+use crate::dom::media_query_raw;
 use crate::operations::spawn_future;
-use crate::utils::UnwrapJsExt;
+use crate::utils::{EventListener, UnwrapJsExt};
 
 
 struct RafState {
@@ -77,8 +82,58 @@ impl Drop for Raf {
 }
 
 
+/// Schedules the frames which drive [`timestamps`], and therefore all of the animations.
+///
+/// By default this uses `requestAnimationFrame`, you can use [`set_frame_source`] to replace it,
+/// e.g. for server-side rendering. For tests use `testing::use_virtual_clock` (with the `testing` feature).
+pub trait FrameSource {
+    /// Calls `callback` with the current time (in milliseconds) once per frame, until [`stop`](FrameSource::stop) is called.
+    fn start(&self, callback: Box<dyn FnMut(f64)>);
+
+    /// This might be called inside of the callback.
+    fn stop(&self);
+}
+
+struct RafSource {
+    raf: RefCell<Option<Raf>>,
+}
+
+impl FrameSource for RafSource {
+    #[inline]
+    fn start(&self, callback: Box<dyn FnMut(f64)>) {
+        *self.raf.borrow_mut() = Some(Raf::new(callback));
+    }
+
+    #[inline]
+    fn stop(&self) {
+        *self.raf.borrow_mut() = None;
+    }
+}
+
+thread_local! {
+    static FRAME_SOURCE: RefCell<Rc<dyn FrameSource>> = RefCell::new(Rc::new(RafSource { raf: RefCell::new(None) }));
+}
+
+#[inline]
+fn frame_source() -> Rc<dyn FrameSource> {
+    FRAME_SOURCE.with(|source| source.borrow().clone())
+}
+
+/// Replaces the [`FrameSource`] for the current thread.
+///
+/// This should be called before any animations are created.
+pub fn set_frame_source<A>(source: A) where A: FrameSource + 'static {
+    let old = FRAME_SOURCE.with(|x| x.replace(Rc::new(source)));
+    old.stop();
+
+    TIMESTAMPS_MANAGER.with(|manager| {
+        manager.borrow_mut().running = false;
+    });
+}
+
+
 struct TimestampsManager {
-    raf: Option<Raf>,
+    running: bool,
     // TODO make this more efficient
     states: Vec<Weak<Mutex<TimestampsState>>>,
 }
@@ -86,7 +141,7 @@ struct TimestampsManager {
 impl TimestampsManager {
     fn new() -> Self {
         Self {
-            raf: None,
+            running: false,
             states: vec![],
         }
     }
@@ -157,10 +212,12 @@ pub fn timestamps() -> Timestamps {
 
             lock.states.push(Arc::downgrade(&timestamps.state));
 
-            if let None = lock.raf {
+            if !lock.running {
+                lock.running = true;
+
                 let timestamps_manager = timestamps_manager.clone();
 
-                lock.raf = Some(Raf::new(move |time| {
+                frame_source().start(Box::new(move |time| {
                     let mut lock = timestamps_manager.borrow_mut();
 
                     lock.states.retain(|state| {
@@ -183,9 +240,11 @@ pub fn timestamps() -> Timestamps {
                     });
 
                     if lock.states.len() == 0 {
-                        lock.raf = None;
+                        lock.running = false;
                         // TODO is this a good idea ?
                         lock.states = vec![];
+                        drop(lock);
+                        frame_source().stop();
                     }
                 }));
             }
@@ -212,6 +271,7 @@ impl<S: SignalVec> AnimatedSignalVec for S {
         where F: FnMut(Self::Item, Self::Animation) -> A {
         AnimatedMap {
             duration: duration,
+            reduced_motion: None,
             animations: vec![],
             signal: Some(self),
             callback: f,
@@ -243,6 +303,7 @@ struct AnimatedMapState {
 #[derive(Debug)]
 pub struct AnimatedMap<A, B> {
     duration: f64,
+    reduced_motion: Option<ReducedMotion>,
     animations: Vec<AnimatedMapState>,
     #[pin]
     signal: Option<A>,
@@ -253,9 +314,30 @@ impl<A, F, S> AnimatedMap<S, F>
     where S: SignalVec,
           F: FnMut(S::Item, AnimatedMapBroadcaster) -> A {
 
-    fn animated_state(duration: f64) -> AnimatedMapState {
+    /// Overrides the global [`ReducedMotion`] policy for the insert and remove animations.
+    ///
+    /// This takes effect the next time an animation starts, including for existing children.
+    pub fn set_reduced_motion(&mut self, policy: ReducedMotion) {
+        self.reduced_motion = Some(policy);
+
+        for state in self.animations.iter() {
+            state.animation.set_reduced_motion(policy);
+        }
+    }
+
+    fn new_animation(duration: f64, initial: Percentage, reduced_motion: Option<ReducedMotion>) -> MutableAnimation {
+        let animation = MutableAnimation::new_with_initial(duration, initial);
+
+        if let Some(policy) = reduced_motion {
+            animation.set_reduced_motion(policy);
+        }
+
+        animation
+    }
+
+    fn animated_state(duration: f64, reduced_motion: Option<ReducedMotion>) -> AnimatedMapState {
         let state = AnimatedMapState {
-            animation: MutableAnimation::new(duration),
+            animation: Self::new_animation(duration, Percentage::START, reduced_motion),
             removing: None,
         };
 
@@ -326,7 +408,7 @@ impl<A, F, S> SignalVec for AnimatedMap<S, F>
     fn poll_vec_change(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<VecDiff<Self::Item>>> {
         let mut is_done = true;
 
-        let AnimatedMapProj { mut animations, mut signal, callback, duration, .. } = self.project();
+        let AnimatedMapProj { mut animations, mut signal, callback, duration, reduced_motion } = self.project();
 
         // TODO is this loop correct ?
         while let Some(result) = signal.as_mut().as_pin_mut().map(|signal| signal.poll_vec_change(cx)) {
@@ -339,7 +421,7 @@ impl<A, F, S> SignalVec for AnimatedMap<S, F>
                         Poll::Ready(Some(VecDiff::Replace {
                             values: values.into_iter().map(|value| {
                                 let state = AnimatedMapState {
-                                    animation: MutableAnimation::new_with_initial(*duration, Percentage::new_unchecked(1.0)),
+                                    animation: Self::new_animation(*duration, Percentage::END, *reduced_motion),
                                     removing: None,
                                 };
 
@@ -354,14 +436,14 @@ impl<A, F, S> SignalVec for AnimatedMap<S, F>
 
                     VecDiff::InsertAt { index, value } => {
                         let index = Self::find_index(&animations, index).unwrap_or_else(|| animations.len());
-                        let state = Self::animated_state(*duration);
+                        let state = Self::animated_state(*duration, *reduced_motion);
                         let value = callback(value, AnimatedMapBroadcaster(state.animation.raw_clone()));
                         animations.insert(index, state);
                         Poll::Ready(Some(VecDiff::InsertAt { index, value }))
                     },
 
                     VecDiff::Push { value } => {
-                        let state = Self::animated_state(*duration);
+                        let state = Self::animated_state(*duration, *reduced_motion);
                         let value = callback(value, AnimatedMapBroadcaster(state.animation.raw_clone()));
                         animations.push(state);
                         Poll::Ready(Some(VecDiff::Push { value }))
@@ -603,6 +685,115 @@ impl fmt::Debug for OnTimestampDiff {
     }
 }
 
+/// What happens to animations when the user [prefers reduced motion](https://developer.mozilla.org/en-US/docs/Web/CSS/@media/prefers-reduced-motion).
+///
+/// The global policy is set with [`set_reduced_motion`], and it can be overridden for individual
+/// animations, e.g. with [`MutableAnimation::set_reduced_motion`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReducedMotion {
+    /// Animations immediately jump to their end state.
+    #[default]
+    Jump,
+
+    /// Animations take at most this many milliseconds.
+    ///
+    /// Springs don't have a duration, so they jump instead.
+    Duration(f64),
+
+    /// Animations run normally, this is useful for animations which are essential.
+    Animate,
+}
+
+impl ReducedMotion {
+    /// Returns the policy which should be used, or `None` if the user doesn't prefer reduced motion.
+    fn current(policy: Option<Self>) -> Option<Self> {
+        if prefers_reduced_motion() {
+            Some(policy.unwrap_or_else(|| REDUCED_MOTION.with(|x| x.get())))
+
+        } else {
+            None
+        }
+    }
+
+    /// Returns the duration which should be used, based on the user's preference.
+    pub(crate) fn duration(policy: Option<Self>, duration: f64) -> f64 {
+        match Self::current(policy) {
+            Some(Self::Jump) => 0.0,
+            Some(Self::Duration(max)) => duration.min(max),
+            Some(Self::Animate) | None => duration,
+        }
+    }
+
+    /// Whether animations without a duration (like springs) should jump.
+    #[inline]
+    fn should_jump(policy: Option<Self>) -> bool {
+        match Self::current(policy) {
+            Some(Self::Jump) | Some(Self::Duration(_)) => true,
+            Some(Self::Animate) | None => false,
+        }
+    }
+}
+
+
+struct PrefersReducedMotion {
+    mutable: Mutable<bool>,
+    _listener: Option<EventListener>,
+}
+
+impl PrefersReducedMotion {
+    fn new() -> Self {
+        // The virtual clock doesn't have a browser
+        #[cfg(any(test, feature = "testing"))]
+        {
+            if testing::is_enabled() {
+                return Self {
+                    mutable: Mutable::new(false),
+                    _listener: None,
+                };
+            }
+        }
+
+        let (mutable, listener) = media_query_raw("(prefers-reduced-motion: reduce)", |value| value);
+
+        Self {
+            mutable,
+            _listener: Some(listener),
+        }
+    }
+}
+
+thread_local! {
+    static REDUCED_MOTION: Cell<ReducedMotion> = const { Cell::new(ReducedMotion::Jump) };
+
+    // This is created lazily and never removed, because it is needed by every animation
+    static PREFERS_REDUCED_MOTION: RefCell<Option<PrefersReducedMotion>> = const { RefCell::new(None) };
+}
+
+fn prefers_reduced_motion_mutable() -> Mutable<bool> {
+    PREFERS_REDUCED_MOTION.with(|x| {
+        x.borrow_mut().get_or_insert_with(PrefersReducedMotion::new).mutable.clone()
+    })
+}
+
+/// Sets the global [`ReducedMotion`] policy, the default is [`ReducedMotion::Jump`].
+#[inline]
+pub fn set_reduced_motion(policy: ReducedMotion) {
+    REDUCED_MOTION.with(|x| x.set(policy));
+}
+
+/// Whether the user prefers reduced motion.
+#[inline]
+pub fn prefers_reduced_motion() -> bool {
+    prefers_reduced_motion_mutable().get()
+}
+
+/// The same as [`prefers_reduced_motion`], except it updates when the user's preference changes.
+#[inline]
+pub fn prefers_reduced_motion_signal() -> impl Signal<Item = bool> {
+    prefers_reduced_motion_mutable().signal()
+}
+
+
 
 #[derive(Debug)]
 pub struct MutableAnimationSignal(MutableSignal<Percentage>);
@@ -617,22 +808,90 @@ impl Signal for MutableAnimationSignal {
 }
 
 
+/// The error for [`MutableAnimation::animate_to_future`] when the animation doesn't reach its end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationInterrupted;
+
+impl fmt::Display for AnimationInterrupted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("the animation was interrupted")
+    }
+}
+
+impl std::error::Error for AnimationInterrupted {}
+
+
+type FinishedCallback = Box<dyn FnMut(Percentage) + Send>;
+
+struct FinishedCallbacks {
+    next_id: usize,
+    callbacks: Vec<(usize, FinishedCallback)>,
+    // Whether the callbacks are currently being called
+    running: bool,
+    // The animation finished again while the callbacks were running
+    queued: Vec<Percentage>,
+    // The callbacks which were removed while they were running
+    removed: Vec<usize>,
+}
+
+impl FinishedCallbacks {
+    fn remove(&mut self, id: usize) {
+        let len = self.callbacks.len();
+
+        self.callbacks.retain(|(x, _)| *x != id);
+
+        if self.running && self.callbacks.len() == len {
+            self.removed.push(id);
+        }
+    }
+}
+
+
+/// This is returned by [`MutableAnimation::on_finished`].
+///
+/// If you never call `handle.discard()` then the callback will exist for as long as the animation exists.
+#[must_use]
+pub struct FinishedHandle {
+    inner: Weak<MutableAnimationInner>,
+    id: usize,
+}
+
+impl Discard for FinishedHandle {
+    fn discard(self) {
+        if let Some(inner) = self.inner.upgrade() {
+            inner.on_finished.lock().unwrap_throw().remove(self.id);
+        }
+    }
+}
+
 // TODO verify that this is Sync and Send
 struct MutableAnimationState {
     playing: bool,
     duration: f64,
     end: Percentage,
+    reduced_motion: Option<ReducedMotion>,
+    waiting: Vec<oneshot::Sender<Result<(), AnimationInterrupted>>>,
     _animating: Option<OnTimestampDiff>,
 }
 
+impl MutableAnimationState {
+    fn resolve(&mut self, result: Result<(), AnimationInterrupted>) {
+        for sender in self.waiting.drain(..) {
+            // The future might already be dropped
+            let _ = sender.send(result);
+        }
+    }
+}
+
 struct MutableAnimationInner {
     state: Mutex<MutableAnimationState>,
     value: Mutable<Percentage>,
+    is_playing: Mutable<bool>,
+    on_finished: Mutex<FinishedCallbacks>,
 }
 
 // TODO deref to ReadOnlyMutable ?
 // TODO provide read_only() method ?
-// TODO add `is_playing` method ?
 #[derive(Clone)]
 pub struct MutableAnimation {
     inner: Arc<MutableAnimationInner>,
@@ -644,6 +903,7 @@ impl fmt::Debug for MutableAnimation {
 
         fmt.debug_struct("MutableAnimation")
             .field("playing", &state.playing)
+            .field("is_playing", &self.inner.is_playing.get())
             .field("duration", &state.duration)
             .field("current", &self.inner.value.get())
             .field("end", &state.end)
@@ -662,9 +922,19 @@ impl MutableAnimation {
                     playing: true,
                     duration: duration,
                     end: initial,
+                    reduced_motion: None,
+                    waiting: vec![],
                     _animating: None,
                 }),
                 value: Mutable::new(initial),
+                is_playing: Mutable::new(false),
+                on_finished: Mutex::new(FinishedCallbacks {
+                    next_id: 0,
+                    callbacks: vec![],
+                    running: false,
+                    queued: vec![],
+                    removed: vec![],
+                }),
             }),
         }
     }
@@ -682,19 +952,69 @@ impl MutableAnimation {
     }
 
     #[inline]
-    fn stop_animating(lock: &mut MutableAnimationState) {
+    fn stop_animating(&self, lock: &mut MutableAnimationState) {
         lock._animating = None;
+        self.inner.is_playing.set_neq(false);
+    }
+
+    /// Runs the [`on_finished`](MutableAnimation::on_finished) callbacks.
+    ///
+    /// This must be called after the state lock is released, because the callbacks can change the animation.
+    ///
+    /// If a callback causes the animation to immediately finish again, then the callbacks are called
+    /// again after they have all finished.
+    fn finished(&self, mut end: Percentage) {
+        let mut callbacks = {
+            let mut lock = self.inner.on_finished.lock().unwrap_throw();
+
+            if lock.running {
+                lock.queued.push(end);
+                return;
+            }
+
+            lock.running = true;
+            std::mem::take(&mut lock.callbacks)
+        };
+
+        loop {
+            for (id, callback) in callbacks.iter_mut() {
+                // A previous callback might have removed this callback
+                if !self.inner.on_finished.lock().unwrap_throw().removed.contains(id) {
+                    callback(end);
+                }
+            }
+
+            let mut lock = self.inner.on_finished.lock().unwrap_throw();
+
+            let removed = std::mem::take(&mut lock.removed);
+            callbacks.retain(|(id, _)| !removed.contains(id));
+
+            // The callbacks might have added more callbacks
+            callbacks.append(&mut lock.callbacks);
+
+            if lock.queued.is_empty() {
+                lock.callbacks = callbacks;
+                lock.running = false;
+                break;
+
+            } else {
+                end = lock.queued.remove(0);
+            }
+        }
     }
 
-    fn start_animating(&self, lock: &mut MutableAnimationState) {
+    /// Returns `true` if the animation immediately finished.
+    fn start_animating(&self, lock: &mut MutableAnimationState) -> bool {
         if lock.playing {
             // TODO use Copy constraint to make value.get() faster ?
             let start: f64 = self.inner.value.get().into_f64();
             let end: f64 = lock.end.into_f64();
 
             if start != end {
-                if lock.duration > 0.0 {
-                    let duration = (end - start).abs() * lock.duration;
+                let duration = ReducedMotion::duration(lock.reduced_motion, lock.duration);
+
+                if duration > 0.0 {
+                    let duration = (end - start).abs() * duration;
 
                     let state = self.raw_clone();
 
@@ -705,24 +1025,40 @@ impl MutableAnimation {
                         if diff >= 1.0 {
                             {
                                 let mut lock = state.inner.state.lock().unwrap_throw();
-                                Self::stop_animating(&mut lock);
+                                state.stop_animating(&mut lock);
+                                lock.resolve(Ok(()));
                             }
                             state.inner.value.set_neq(Percentage::new_unchecked(end));
+                            state.finished(Percentage::new_unchecked(end));
 
                         } else {
                             state.inner.value.set_neq(Percentage::new_unchecked(range_inclusive(diff, start, end)));
                         }
                     }));
 
+                    self.inner.is_playing.set_neq(true);
+
+                    false
+
                 } else {
-                    Self::stop_animating(lock);
+                    self.stop_animating(lock);
                     self.inner.value.set_neq(Percentage::new_unchecked(end));
+                    lock.resolve(Ok(()));
+                    true
                 }
 
             } else {
+                let was_animating = lock._animating.is_some();
+
                 // TODO is this necessary ?
-                Self::stop_animating(lock);
+                self.stop_animating(lock);
+                lock.resolve(Ok(()));
+
+                was_animating
             }
+
+        } else {
+            false
         }
     }
 
@@ -733,17 +1069,30 @@ impl MutableAnimation {
 
         if lock.duration != duration {
             lock.duration = duration;
-            self.start_animating(&mut lock);
+
+            if self.start_animating(&mut lock) {
+                let end = lock.end;
+                drop(lock);
+                self.finished(end);
+            }
         }
     }
 
+    /// Overrides the global [`ReducedMotion`] policy for this animation.
+    ///
+    /// This takes effect the next time the animation starts.
+    #[inline]
+    pub fn set_reduced_motion(&self, policy: ReducedMotion) {
+        self.inner.state.lock().unwrap_throw().reduced_motion = Some(policy);
+    }
+
     #[inline]
     pub fn pause(&self) {
         let mut lock = self.inner.state.lock().unwrap_throw();
 
         if lock.playing {
             lock.playing = false;
-            Self::stop_animating(&mut lock);
+            self.stop_animating(&mut lock);
         }
     }
 
@@ -753,36 +1102,116 @@ impl MutableAnimation {
 
         if !lock.playing {
             lock.playing = true;
-            self.start_animating(&mut lock);
+
+            if self.start_animating(&mut lock) {
+                let end = lock.end;
+                drop(lock);
+                self.finished(end);
+            }
         }
     }
 
-    fn _jump_to(mut lock: &mut MutableAnimationState, mutable: &Mutable<Percentage>, end: Percentage) {
-        Self::stop_animating(&mut lock);
+    fn _jump_to(&self, lock: &mut MutableAnimationState, end: Percentage) {
+        self.stop_animating(lock);
 
         lock.end = end;
 
-        mutable.set_neq(end);
+        self.inner.value.set_neq(end);
     }
 
+    /// Immediately jumps to `end`.
+    ///
+    /// If the animation was going somewhere else, then [`animate_to_future`](MutableAnimation::animate_to_future) is interrupted.
     pub fn jump_to(&self, end: Percentage) {
         let mut lock = self.inner.state.lock().unwrap_throw();
 
-        Self::_jump_to(&mut lock, &self.inner.value, end);
+        if lock.end == end {
+            lock.resolve(Ok(()));
+
+        } else {
+            lock.resolve(Err(AnimationInterrupted));
+        }
+
+        self._jump_to(&mut lock, end);
     }
 
     pub fn animate_to(&self, end: Percentage) {
         let mut lock = self.inner.state.lock().unwrap_throw();
 
         if lock.end != end {
-            if lock.duration <= 0.0 {
-                Self::_jump_to(&mut lock, &self.inner.value, end);
+            // The previous animation didn't reach its end
+            lock.resolve(Err(AnimationInterrupted));
+
+            if ReducedMotion::duration(lock.reduced_motion, lock.duration) <= 0.0 {
+                self._jump_to(&mut lock, end);
+                drop(lock);
+                self.finished(end);
 
             } else {
                 lock.end = end;
-                self.start_animating(&mut lock);
+
+                if self.start_animating(&mut lock) {
+                    drop(lock);
+                    self.finished(end);
+                }
+            }
+        }
+    }
+
+    /// The same as [`animate_to`](MutableAnimation::animate_to), except it returns a `Future` which
+    /// resolves when the animation reaches `end`.
+    ///
+    /// If the animation is changed to go somewhere else before it reaches `end`, then the `Future` returns `Err`.
+    ///
+    /// If the animation is paused, then the `Future` waits until it is played again.
+    pub fn animate_to_future(&self, end: Percentage) -> impl Future<Output = Result<(), AnimationInterrupted>> {
+        self.animate_to(end);
+
+        let (sender, receiver) = oneshot::channel();
+
+        {
+            let mut lock = self.inner.state.lock().unwrap_throw();
+
+            if lock.end == end && lock._animating.is_none() && self.inner.value.get() == end {
+                // The receiver is still alive, so this can't fail
+                let _ = sender.send(Ok(()));
+
+            } else {
+                lock.waiting.push(sender);
             }
         }
+
+        // If the animation is dropped then it will never reach the end
+        receiver.map(|result| result.unwrap_or(Err(AnimationInterrupted)))
+    }
+
+    /// Calls `f` every time that the animation reaches its end, this is not called for [`jump_to`](MutableAnimation::jump_to).
+    ///
+    /// The callback is kept until the [`FinishedHandle`] is discarded. If the callback contains
+    /// the animation then it must be discarded, otherwise the animation will leak.
+    pub fn on_finished<F>(&self, f: F) -> FinishedHandle where F: FnMut(Percentage) + Send + 'static {
+        let mut lock = self.inner.on_finished.lock().unwrap_throw();
+
+        let id = lock.next_id;
+        lock.next_id += 1;
+        lock.callbacks.push((id, Box::new(f)));
+
+        FinishedHandle {
+            inner: Arc::downgrade(&self.inner),
+            id,
+        }
+    }
+
+    /// Whether the animation is currently moving, this is `false` if it is paused or finished.
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.inner.is_playing.get()
+    }
+
+    /// The same as [`is_playing`](MutableAnimation::is_playing), except it updates when the animation starts or stops.
+    #[inline]
+    pub fn is_playing_signal(&self) -> impl Signal<Item = bool> {
+        self.inner.is_playing.signal()
     }
 
     #[inline]
@@ -797,96 +1226,1777 @@ impl MutableAnimation {
 }
 
 
-pub mod easing {
-    use super::Percentage;
+/// The physical properties of a [`MutableSpring`].
+///
+/// The defaults are a fairly stiff spring which settles quickly without bouncing too much.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpringConfig {
+    pub mass: f64,
+    pub stiffness: f64,
+    pub damping: f64,
+    /// The spring is settled when both the distance to the target and the velocity are below this.
+    pub precision: f64,
+}
 
-    // TODO should this use map rather than map_unchecked ?
+impl Default for SpringConfig {
     #[inline]
-    pub fn powi(p: Percentage, n: i32) -> Percentage {
-        p.map_unchecked(|p| p.powi(n))
+    fn default() -> Self {
+        Self {
+            mass: 1.0,
+            stiffness: 170.0,
+            damping: 26.0,
+            precision: 0.001,
+        }
     }
+}
 
-    #[inline]
-    pub fn cubic(p: Percentage) -> Percentage {
-        powi(p, 3)
-    }
 
-    #[inline]
-    pub fn out<F>(p: Percentage, f: F) -> Percentage where F: FnOnce(Percentage) -> Percentage {
-        f(p.invert()).invert()
-    }
+#[derive(Debug)]
+pub struct MutableSpringSignal(MutableSignal<f64>);
 
-    pub fn in_out<F>(p: Percentage, f: F) -> Percentage where F: FnOnce(Percentage) -> Percentage {
-        p.map_unchecked(|p| {
-            if p <= 0.5 {
-                f(Percentage::new_unchecked(p * 2.0)).into_f64() / 2.0
+impl Signal for MutableSpringSignal {
+    type Item = f64;
 
-            } else {
-                1.0 - (f(Percentage::new_unchecked((1.0 - p) * 2.0)).into_f64() / 2.0)
-            }
-        })
+    #[inline]
+    fn poll_change(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.0.poll_change_unpin(cx)
     }
+}
 
 
-    /*pub struct Point {
-        pub x: f64,
-        pub y: f64,
-    }*/
+struct MutableSpringState {
+    config: SpringConfig,
+    target: f64,
+    velocity: f64,
+    reduced_motion: Option<ReducedMotion>,
+    _animating: Option<DiscardOnDrop<CancelableFutureHandle>>,
+}
 
-    /*impl Point {
-        fn range_inclusive(percentage: f64, from: &Self, to: &Self) -> Self {
-            Point {
-                x: range_inclusive(percentage, from.x, to.x),
-                y: range_inclusive(percentage, from.y, to.y),
-            }
-        }
-    }*/
+impl MutableSpringState {
+    // The spring is integrated in fixed steps, so that it behaves the same regardless of the framerate
+    const STEP: f64 = 0.001;
 
-    /*#[inline]
-    fn get_values(start: f64, end: f64) -> (f64, f64, f64) {
-        let start = 3.0 * start;
-        let end = 3.0 * end;
-        let a = 1.0 - end + start;
-        let b = end - (2.0 * start);
-        (a, b, start)
-    }
+    // If a frame takes longer than this (e.g. the tab was in the background) then the extra time is skipped
+    const MAX_FRAME: f64 = 0.064;
 
-    fn interpolate(p: f64, start: f64, end: f64) -> f64 {
-        let (a, b, c) = get_values(start, end);
-        ((a * p + b) * p + c) * p
-    }
+    /// Advances the spring by `dt` seconds, returns `true` if the spring is settled.
+    fn step(&mut self, value: &mut f64, dt: f64) -> bool {
+        let mut remaining = dt.min(Self::MAX_FRAME);
 
-    fn get_slope(p: f64, start: f64, end: f64) -> f64 {
-        let (a, b, c) = get_values(start, end);
-        (3.0 * a * p * p) + (2.0 * b * p) + c
-    }*/
+        while remaining > 0.0 {
+            let dt = remaining.min(Self::STEP);
 
+            let force = -self.config.stiffness * (*value - self.target) - self.config.damping * self.velocity;
 
-    const EPSILON: f64 = 1e-6;
+            // Semi-implicit Euler, this is stable enough for UI springs
+            self.velocity += (force / self.config.mass) * dt;
+            *value += self.velocity * dt;
 
-    pub struct CubicBezier {
-        ax: f64,
-        bx: f64,
-        cx: f64,
+            remaining -= dt;
+        }
 
-        ay: f64,
-        by: f64,
-        cy: f64,
+        if self.velocity.abs() < self.config.precision && (*value - self.target).abs() < self.config.precision {
+            self.velocity = 0.0;
+            *value = self.target;
+            true
+
+        } else {
+            false
+        }
     }
+}
 
-    impl CubicBezier {
-        pub fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
-            assert!(x1 >= 0.0 && x1 <= 1.0);
-            assert!(y1 >= 0.0 && y1 <= 1.0);
-            assert!(x2 >= 0.0 && x2 <= 1.0);
-            assert!(y2 >= 0.0 && y2 <= 1.0);
+struct MutableSpringInner {
+    state: Mutex<MutableSpringState>,
+    value: Mutable<f64>,
+}
 
-            let cx = 3.0 * x1;
-            let bx = 3.0 * (x2 - x1) - cx;
-            let ax = 1.0 - cx - bx;
+/// An animated value which is driven by spring physics rather than a fixed duration.
+///
+/// Unlike [`MutableAnimation`], changing the target while the spring is moving keeps the
+/// current velocity, so interrupted animations stay smooth.
+///
+/// The spring only requests animation frames while it is moving.
+#[derive(Clone)]
+pub struct MutableSpring {
+    inner: Arc<MutableSpringInner>,
+}
 
-            let cy = 3.0 * y1;
-            let by = 3.0 * (y2 - y1) - cy;
+impl fmt::Debug for MutableSpring {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock().unwrap_throw();
+
+        fmt.debug_struct("MutableSpring")
+            .field("config", &state.config)
+            .field("current", &self.inner.value.get())
+            .field("target", &state.target)
+            .field("velocity", &state.velocity)
+            .finish()
+    }
+}
+
+impl MutableSpring {
+    #[inline]
+    pub fn new_with_config(initial: f64, config: SpringConfig) -> Self {
+        debug_assert!(config.mass > 0.0);
+        debug_assert!(config.precision > 0.0);
+
+        Self {
+            inner: Arc::new(MutableSpringInner {
+                state: Mutex::new(MutableSpringState {
+                    config,
+                    target: initial,
+                    velocity: 0.0,
+                    reduced_motion: None,
+                    _animating: None,
+                }),
+                value: Mutable::new(initial),
+            }),
+        }
+    }
+
+    #[inline]
+    pub fn new(initial: f64) -> Self {
+        Self::new_with_config(initial, SpringConfig::default())
+    }
+
+    #[inline]
+    fn raw_clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn start_animating(&self, lock: &mut MutableSpringState) {
+        if lock._animating.is_none() {
+            let state = self.raw_clone();
+
+            lock._animating = Some(spawn_future(
+                timestamps_difference()
+                    .for_each(move |diff| {
+                        if let Some(diff) = diff {
+                            let mut lock = state.inner.state.lock().unwrap_throw();
+
+                            let mut value = state.inner.value.get();
+
+                            if lock.step(&mut value, diff / 1000.0) {
+                                lock._animating = None;
+                            }
+
+                            state.inner.value.set_neq(value);
+                        }
+
+                        ready(())
+                    })
+            ));
+        }
+    }
+
+    pub fn set_config(&self, config: SpringConfig) {
+        debug_assert!(config.mass > 0.0);
+        debug_assert!(config.precision > 0.0);
+
+        let mut lock = self.inner.state.lock().unwrap_throw();
+        lock.config = config;
+    }
+
+    /// Overrides the global [`ReducedMotion`] policy for this spring.
+    #[inline]
+    pub fn set_reduced_motion(&self, policy: ReducedMotion) {
+        self.inner.state.lock().unwrap_throw().reduced_motion = Some(policy);
+    }
+
+    fn _jump_to(&self, lock: &mut MutableSpringState, value: f64) {
+        lock._animating = None;
+        lock.target = value;
+        lock.velocity = 0.0;
+
+        self.inner.value.set_neq(value);
+    }
+
+    /// Starts moving towards `target`, keeping the current velocity.
+    pub fn animate_to(&self, target: f64) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        if ReducedMotion::should_jump(lock.reduced_motion) {
+            self._jump_to(&mut lock, target);
+
+        } else if lock.target != target {
+            lock.target = target;
+            self.start_animating(&mut lock);
+        }
+    }
+
+    /// Immediately moves to `value` and stops the spring.
+    pub fn jump_to(&self, value: f64) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+        self._jump_to(&mut lock, value);
+    }
+
+    /// Adds to the current velocity (in units per second), e.g. at the end of a drag gesture.
+    pub fn add_velocity(&self, velocity: f64) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        if velocity != 0.0 && !ReducedMotion::should_jump(lock.reduced_motion) {
+            lock.velocity += velocity;
+            self.start_animating(&mut lock);
+        }
+    }
+
+    #[inline]
+    pub fn signal(&self) -> MutableSpringSignal {
+        MutableSpringSignal(self.inner.value.signal())
+    }
+
+    #[inline]
+    pub fn current_value(&self) -> f64 {
+        self.inner.value.get()
+    }
+
+    #[inline]
+    pub fn target(&self) -> f64 {
+        self.inner.state.lock().unwrap_throw().target
+    }
+
+    #[inline]
+    pub fn velocity(&self) -> f64 {
+        self.inner.state.lock().unwrap_throw().velocity
+    }
+
+    #[inline]
+    pub fn is_settled(&self) -> bool {
+        self.inner.state.lock().unwrap_throw()._animating.is_none()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+struct TimelineEntry {
+    animation: MutableAnimation,
+    start: f64,
+    duration: f64,
+}
+
+impl TimelineEntry {
+    fn percentage(&self, position: f64) -> Percentage {
+        if position <= self.start {
+            Percentage::new_unchecked(0.0)
+
+        } else if position >= self.start + self.duration {
+            Percentage::new_unchecked(1.0)
+
+        } else {
+            Percentage::new_unchecked((position - self.start) / self.duration)
+        }
+    }
+}
+
+/// A piece of a [`Timeline`].
+///
+/// Steps can be nested, e.g. a sequence of parallel steps:
+///
+/// ```rust
+/// let timeline = Timeline::new(TimelineStep::sequence(vec![
+///     TimelineStep::animate(&fade, 300.0),
+///     TimelineStep::delay(100.0),
+///     TimelineStep::stagger(50.0, items.iter().map(|item| TimelineStep::animate(item, 200.0))),
+/// ]));
+///
+/// timeline.play();
+/// ```
+#[derive(Debug, Clone)]
+pub struct TimelineStep {
+    entries: Vec<TimelineEntry>,
+    duration: f64,
+}
+
+impl TimelineStep {
+    /// Animates `animation` from `0` to `1` over `duration` milliseconds.
+    pub fn animate(animation: &MutableAnimation, duration: f64) -> Self {
+        debug_assert!(duration >= 0.0);
+
+        Self {
+            entries: vec![TimelineEntry {
+                animation: animation.raw_clone(),
+                start: 0.0,
+                duration,
+            }],
+            duration,
+        }
+    }
+
+    /// Does nothing for `duration` milliseconds.
+    pub fn delay(duration: f64) -> Self {
+        debug_assert!(duration >= 0.0);
+
+        Self {
+            entries: vec![],
+            duration,
+        }
+    }
+
+    /// Runs the steps one after another.
+    pub fn sequence<I>(steps: I) -> Self where I: IntoIterator<Item = Self> {
+        let mut entries = vec![];
+        let mut duration = 0.0;
+
+        for step in steps {
+            entries.extend(step.offset(duration));
+            duration += step.duration;
+        }
+
+        Self { entries, duration }
+    }
+
+    /// Runs the steps at the same time, it finishes when the longest step finishes.
+    #[inline]
+    pub fn parallel<I>(steps: I) -> Self where I: IntoIterator<Item = Self> {
+        Self::stagger(0.0, steps)
+    }
+
+    /// Runs the steps at the same time, but each step starts `offset` milliseconds after the previous step.
+    pub fn stagger<I>(offset: f64, steps: I) -> Self where I: IntoIterator<Item = Self> {
+        debug_assert!(offset >= 0.0);
+
+        let mut entries = vec![];
+        let mut duration: f64 = 0.0;
+
+        for (index, step) in steps.into_iter().enumerate() {
+            let start = offset * (index as f64);
+            entries.extend(step.offset(start));
+            duration = duration.max(start + step.duration);
+        }
+
+        Self { entries, duration }
+    }
+
+    /// Waits for `delay` milliseconds before running this step.
+    #[inline]
+    pub fn with_delay(self, delay: f64) -> Self {
+        Self::sequence(vec![Self::delay(delay), self])
+    }
+
+    #[inline]
+    pub fn duration(&self) -> f64 {
+        self.duration
+    }
+
+    fn offset(&self, start: f64) -> impl Iterator<Item = TimelineEntry> + '_ {
+        self.entries.iter().map(move |entry| TimelineEntry {
+            animation: entry.animation.raw_clone(),
+            start: entry.start + start,
+            duration: entry.duration,
+        })
+    }
+}
+
+
+struct TimelineState {
+    playing: bool,
+    reversed: bool,
+    position: f64,
+    _animating: Option<OnTimestampDiff>,
+}
+
+struct TimelineInner {
+    entries: Vec<TimelineEntry>,
+    duration: f64,
+    state: Mutex<TimelineState>,
+    progress: Mutable<Percentage>,
+}
+
+/// Plays multiple [`MutableAnimation`]s according to a [`TimelineStep`].
+///
+/// The timeline controls the animations by jumping them to the right percentage on every frame,
+/// so their signals can be used as usual. The timeline starts paused at the beginning.
+#[derive(Clone)]
+pub struct Timeline {
+    inner: Arc<TimelineInner>,
+}
+
+impl fmt::Debug for Timeline {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.inner.state.lock().unwrap_throw();
+
+        fmt.debug_struct("Timeline")
+            .field("playing", &state.playing)
+            .field("reversed", &state.reversed)
+            .field("duration", &self.inner.duration)
+            .field("position", &state.position)
+            .finish()
+    }
+}
+
+impl Timeline {
+    pub fn new(step: TimelineStep) -> Self {
+        let timeline = Self {
+            inner: Arc::new(TimelineInner {
+                entries: step.entries,
+                duration: step.duration,
+                state: Mutex::new(TimelineState {
+                    playing: false,
+                    reversed: false,
+                    position: 0.0,
+                    _animating: None,
+                }),
+                progress: Mutable::new(Percentage::new_unchecked(0.0)),
+            }),
+        };
+
+        timeline.apply(0.0);
+
+        timeline
+    }
+
+    #[inline]
+    fn raw_clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+
+    fn apply(&self, position: f64) {
+        for entry in self.inner.entries.iter() {
+            entry.animation.jump_to(entry.percentage(position));
+        }
+
+        let progress = if self.inner.duration > 0.0 {
+            position / self.inner.duration
+
+        } else {
+            1.0
+        };
+
+        self.inner.progress.set_neq(Percentage::new_unchecked(progress));
+    }
+
+    fn start_animating(&self, lock: &mut TimelineState) {
+        let duration = self.inner.duration;
+        let start = lock.position;
+        let reversed = lock.reversed;
+
+        let end = if reversed { 0.0 } else { duration };
+
+        // With reduced motion the whole timeline is sped up
+        let reduced = ReducedMotion::duration(None, duration);
+
+        if start == end || reduced <= 0.0 {
+            lock.position = end;
+            lock.playing = false;
+            lock._animating = None;
+            self.apply(end);
+            return;
+        }
+
+        let state = self.raw_clone();
+
+        let speed = duration / reduced;
+
+        lock._animating = Some(OnTimestampDiff::new(move |diff| {
+            let diff = diff * speed;
+
+            let position = if reversed {
+                (start - diff).max(0.0)
+
+            } else {
+                (start + diff).min(duration)
+            };
+
+            {
+                let mut lock = state.inner.state.lock().unwrap_throw();
+
+                lock.position = position;
+
+                if position == end {
+                    lock.playing = false;
+                    lock._animating = None;
+                }
+            }
+
+            state.apply(position);
+        }));
+    }
+
+    /// Plays the timeline from the current position.
+    ///
+    /// If the timeline is already at the end then it starts over.
+    pub fn play(&self) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        if !lock.playing {
+            lock.playing = true;
+
+            if lock.reversed {
+                if lock.position <= 0.0 {
+                    lock.position = self.inner.duration;
+                }
+
+            } else if lock.position >= self.inner.duration {
+                lock.position = 0.0;
+            }
+
+            self.start_animating(&mut lock);
+        }
+    }
+
+    pub fn pause(&self) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        if lock.playing {
+            lock.playing = false;
+            lock._animating = None;
+        }
+    }
+
+    /// Changes the direction of the timeline, if it is playing it continues in the new direction.
+    pub fn reverse(&self) {
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        lock.reversed = !lock.reversed;
+
+        if lock.playing {
+            self.start_animating(&mut lock);
+        }
+    }
+
+    /// Jumps to `position` milliseconds, if it is playing it continues from the new position.
+    pub fn seek(&self, position: f64) {
+        let position = position.max(0.0).min(self.inner.duration);
+
+        let mut lock = self.inner.state.lock().unwrap_throw();
+
+        lock.position = position;
+
+        if lock.playing {
+            self.start_animating(&mut lock);
+        }
+
+        drop(lock);
+
+        self.apply(position);
+    }
+
+    #[inline]
+    pub fn seek_percentage(&self, percentage: Percentage) {
+        self.seek(percentage.range_inclusive(0.0, self.inner.duration));
+    }
+
+    #[inline]
+    pub fn is_playing(&self) -> bool {
+        self.inner.state.lock().unwrap_throw().playing
+    }
+
+    #[inline]
+    pub fn is_reversed(&self) -> bool {
+        self.inner.state.lock().unwrap_throw().reversed
+    }
+
+    /// The total duration in milliseconds.
+    #[inline]
+    pub fn duration(&self) -> f64 {
+        self.inner.duration
+    }
+
+    /// The current position in milliseconds.
+    #[inline]
+    pub fn position(&self) -> f64 {
+        self.inner.state.lock().unwrap_throw().position
+    }
+
+    /// The progress of the whole timeline.
+    #[inline]
+    pub fn signal(&self) -> MutableAnimationSignal {
+        MutableAnimationSignal(self.inner.progress.signal())
+    }
+}
+
+
+/// One keyframe for [`DomBuilder::animate`](crate::DomBuilder::animate).
+///
+/// The style names are the same as in CSS, e.g. `"background-color"`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keyframe {
+    offset: Option<f64>,
+    easing: Option<Cow<'static, str>>,
+    styles: Vec<(Cow<'static, str>, Cow<'static, str>)>,
+}
+
+impl Keyframe {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// When this keyframe happens, between `0.0` and `1.0`.
+    ///
+    /// If it isn't specified then the keyframes are spaced evenly.
+    #[inline]
+    pub fn offset(mut self, offset: f64) -> Self {
+        debug_assert!((0.0..=1.0).contains(&offset));
+        self.offset = Some(offset);
+        self
+    }
+
+    /// The [easing](https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function) which is used until the next keyframe.
+    #[inline]
+    pub fn easing<A>(mut self, easing: A) -> Self where A: Into<Cow<'static, str>> {
+        self.easing = Some(easing.into());
+        self
+    }
+
+    #[inline]
+    pub fn style<A, B>(mut self, name: A, value: B) -> Self
+        where A: Into<Cow<'static, str>>,
+              B: Into<Cow<'static, str>> {
+        self.styles.push((name.into(), value.into()));
+        self
+    }
+
+    fn to_js(&self) -> JsValue {
+        let output = js_sys::Object::new();
+
+        if let Some(offset) = self.offset {
+            bindings::set_property(&output, "offset", &JsValue::from(offset));
+        }
+
+        if let Some(easing) = &self.easing {
+            bindings::set_property(&output, "easing", &JsValue::from(easing.as_ref()));
+        }
+
+        for (name, value) in self.styles.iter() {
+            bindings::set_property(&output, &keyframe_property(name), &JsValue::from(value.as_ref()));
+        }
+
+        output.into()
+    }
+}
+
+// Keyframes use the JS names for the properties, e.g. `backgroundColor`
+fn keyframe_property(name: &str) -> Cow<'_, str> {
+    match name {
+        "float" => Cow::Borrowed("cssFloat"),
+        "offset" => Cow::Borrowed("cssOffset"),
+        _ if name.starts_with("--") || !name.contains('-') => Cow::Borrowed(name),
+        _ => {
+            let mut output = String::with_capacity(name.len());
+            let mut upper = false;
+
+            for c in name.chars() {
+                if c == '-' {
+                    upper = true;
+
+                } else if upper {
+                    upper = false;
+                    output.extend(c.to_uppercase());
+
+                } else {
+                    output.push(c);
+                }
+            }
+
+            Cow::Owned(output)
+        },
+    }
+}
+
+fn keyframes_to_js(keyframes: &[Keyframe]) -> js_sys::Array {
+    keyframes.iter().map(Keyframe::to_js).collect()
+}
+
+
+/// How the styles are applied before and after a [`WebAnimation`] is running.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+pub enum FillMode {
+    /// The styles are only applied while the animation is running.
+    None,
+
+    /// The styles of the last keyframe stay applied after the animation finishes.
+    Forwards,
+
+    /// The styles of the first keyframe are applied during the delay.
+    Backwards,
+
+    /// Both [`FillMode::Forwards`] and [`FillMode::Backwards`].
+    Both,
+
+    /// Usually the same as [`FillMode::None`].
+    #[default]
+    Auto,
+}
+
+impl FillMode {
+    fn into_js(self) -> &'static str {
+        match self {
+            Self::None => "none",
+            Self::Forwards => "forwards",
+            Self::Backwards => "backwards",
+            Self::Both => "both",
+            Self::Auto => "auto",
+        }
+    }
+}
+
+/// Which direction each iteration of a [`WebAnimation`] runs.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+pub enum PlaybackDirection {
+    #[default]
+    Normal,
+    Reverse,
+
+    /// Every other iteration runs in reverse.
+    Alternate,
+
+    /// Every other iteration runs in reverse, starting with the first iteration.
+    AlternateReverse,
+}
+
+impl PlaybackDirection {
+    fn into_js(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Reverse => "reverse",
+            Self::Alternate => "alternate",
+            Self::AlternateReverse => "alternate-reverse",
+        }
+    }
+}
+
+/// The timing for [`DomBuilder::animate`](crate::DomBuilder::animate).
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyframeOptions {
+    /// Duration of one iteration in milliseconds.
+    pub duration: f64,
+
+    /// Milliseconds before the animation starts.
+    pub delay: f64,
+
+    /// Milliseconds after the animation ends.
+    pub end_delay: f64,
+
+    /// The [easing](https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function) for the whole animation.
+    pub easing: Cow<'static, str>,
+
+    /// Use [`f64::INFINITY`] to repeat forever.
+    pub iterations: f64,
+
+    pub direction: PlaybackDirection,
+
+    pub fill: FillMode,
+}
+
+impl KeyframeOptions {
+    /// Runs once for `duration` milliseconds.
+    #[inline]
+    pub fn new(duration: f64) -> Self {
+        Self {
+            duration,
+            ..Self::default()
+        }
+    }
+
+    fn to_js(&self) -> JsValue {
+        let output = js_sys::Object::new();
+        bindings::set_property(&output, "duration", &JsValue::from(self.duration));
+        bindings::set_property(&output, "delay", &JsValue::from(self.delay));
+        bindings::set_property(&output, "endDelay", &JsValue::from(self.end_delay));
+        bindings::set_property(&output, "easing", &JsValue::from(self.easing.as_ref()));
+        bindings::set_property(&output, "iterations", &JsValue::from(self.iterations));
+        bindings::set_property(&output, "direction", &JsValue::from(self.direction.into_js()));
+        bindings::set_property(&output, "fill", &JsValue::from(self.fill.into_js()));
+        output.into()
+    }
+}
+
+impl Default for KeyframeOptions {
+    fn default() -> Self {
+        Self {
+            duration: 0.0,
+            delay: 0.0,
+            end_delay: 0.0,
+            easing: Cow::Borrowed("linear"),
+            iterations: 1.0,
+            direction: PlaybackDirection::default(),
+            fill: FillMode::default(),
+        }
+    }
+}
+
+
+/// A handle to a running [Web Animation](https://developer.mozilla.org/en-US/docs/Web/API/Web_Animations_API).
+///
+/// Unlike [`MutableAnimation`], these run in the browser, so animating `transform` and `opacity`
+/// doesn't need to do any work on the main thread.
+#[derive(Debug, Clone)]
+pub struct WebAnimation {
+    animation: web_sys::Animation,
+}
+
+// Returns the new playback rate, or `None` if the animation is already at the beginning (or end)
+//
+// Calling `play` at the beginning with a negative rate (or at the end with a positive rate) would rewind
+// the animation, which replays it from the other end.
+fn toggle_rate(forwards: bool, current_time: Option<f64>, playback_rate: f64, finished: bool) -> Option<f64> {
+    if forwards {
+        if finished && playback_rate > 0.0 {
+            None
+
+        } else {
+            Some(1.0)
+        }
+
+    } else if current_time.unwrap_or(0.0) <= 0.0 {
+        None
+
+    } else {
+        Some(-1.0)
+    }
+}
+
+impl WebAnimation {
+    #[inline]
+    pub fn play(&self) {
+        self.animation.play().unwrap_js();
+    }
+
+    #[inline]
+    pub fn pause(&self) {
+        self.animation.pause().unwrap_js();
+    }
+
+    #[inline]
+    pub fn reverse(&self) {
+        self.animation.reverse().unwrap_js();
+    }
+
+    /// Jumps to the end of the animation.
+    #[inline]
+    pub fn finish(&self) {
+        self.animation.finish().unwrap_js();
+    }
+
+    /// Stops the animation and removes its styles.
+    #[inline]
+    pub fn cancel(&self) {
+        self.animation.cancel();
+    }
+
+    #[inline]
+    pub fn set_playback_rate(&self, rate: f64) {
+        self.animation.set_playback_rate(rate);
+    }
+
+    // Used by `animate_signal`, it doesn't replay the animation when it is already at the beginning or end
+    pub(crate) fn toggle(&self, forwards: bool) {
+        let finished = self.animation.play_state() == web_sys::AnimationPlayState::Finished;
+
+        if let Some(rate) = toggle_rate(forwards, self.animation.current_time(), self.animation.playback_rate(), finished) {
+            // Changing the playback rate keeps the current time, so it continues from the same position
+            self.set_playback_rate(rate);
+            self.play();
+        }
+    }
+
+    /// Whether the animation is currently running.
+    #[inline]
+    pub fn is_running(&self) -> bool {
+        self.animation.play_state() == web_sys::AnimationPlayState::Running
+    }
+
+    // The events have to be listened to before checking the state, otherwise an event could be missed
+    fn ended(&self) -> impl Future<Output = bool> {
+        let (sender, receiver) = oneshot::channel();
+        let sender = Rc::new(RefCell::new(Some(sender)));
+
+        let listen = |name: &'static str, finished: bool| {
+            let sender = sender.clone();
+
+            gloo_events::EventListener::once(&self.animation, name, move |_| {
+                if let Some(sender) = sender.borrow_mut().take() {
+                    // The receiver might already be dropped
+                    let _ = sender.send(finished);
+                }
+            })
+        };
+
+        let listeners = (listen("finish", true), listen("cancel", false));
+
+        let state = self.animation.play_state();
+
+        async move {
+            match state {
+                web_sys::AnimationPlayState::Finished => true,
+                web_sys::AnimationPlayState::Idle => false,
+                _ => {
+                    let finished = receiver.await.unwrap_or(false);
+                    drop(listeners);
+                    finished
+                },
+            }
+        }
+    }
+
+    /// Resolves with `true` when the animation finishes, or `false` if it is cancelled.
+    ///
+    /// If it has already finished (or has been cancelled) then it resolves immediately.
+    #[inline]
+    pub fn finished(&self) -> impl Future<Output = bool> {
+        self.ended()
+    }
+
+    /// Resolves when the animation is cancelled, it never resolves if the animation finishes.
+    pub fn cancelled(&self) -> impl Future<Output = ()> {
+        let ended = self.ended();
+
+        async move {
+            if ended.await {
+                pending::<()>().await;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn raw(&self) -> &web_sys::Animation {
+        &self.animation
+    }
+}
+
+/// Starts a [`WebAnimation`] on the element.
+///
+/// This is what [`DomBuilder::animate`](crate::DomBuilder::animate) uses. Usually you should use
+/// [`DomBuilder::animate_with`](crate::DomBuilder::animate_with) if you need the handle.
+#[inline]
+pub fn animate_element(element: &web_sys::Element, keyframes: &[Keyframe], options: &KeyframeOptions) -> WebAnimation {
+    let options = KeyframeOptions {
+        duration: ReducedMotion::duration(None, options.duration),
+        ..options.clone()
+    };
+
+    WebAnimation {
+        animation: bindings::animate(element, &keyframes_to_js(keyframes), &options.to_js()),
+    }
+}
+
+
+/// Options for [`DomBuilder::children_signal_vec_flip`](crate::DomBuilder::children_signal_vec_flip).
+#[derive(Debug, Clone, Copy)]
+pub struct FlipOptions {
+    /// Duration in milliseconds.
+    pub duration: f64,
+
+    /// One of the functions in [`easing`], or your own function.
+    pub easing: fn(Percentage) -> Percentage,
+}
+
+impl FlipOptions {
+    #[inline]
+    pub fn new(duration: f64) -> Self {
+        debug_assert!(duration >= 0.0);
+
+        Self {
+            duration,
+            easing: |p| easing::out(p, easing::cubic),
+        }
+    }
+}
+
+impl Default for FlipOptions {
+    #[inline]
+    fn default() -> Self {
+        Self::new(300.0)
+    }
+}
+
+
+/// Values which can be smoothly animated with a [`Tween`].
+pub trait Interpolate {
+    /// Returns the value which is `percentage` of the way from `self` to `to`.
+    ///
+    /// The percentage can be outside of `0.0` to `1.0` if the easing overshoots.
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self;
+}
+
+impl Interpolate for f64 {
+    #[inline]
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        percentage.range_inclusive(*self, *to)
+    }
+}
+
+impl Interpolate for f32 {
+    #[inline]
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        percentage.range_inclusive(*self as f64, *to as f64) as f32
+    }
+}
+
+impl<A, const N: usize> Interpolate for [A; N] where A: Interpolate {
+    #[inline]
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        std::array::from_fn(|index| self[index].interpolate(&to[index], percentage))
+    }
+}
+
+macro_rules! interpolate_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name),+> Interpolate for ($($name,)+) where $($name: Interpolate),+ {
+            #[inline]
+            fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+                ($(self.$index.interpolate(&to.$index, percentage),)+)
+            }
+        }
+    };
+}
+
+interpolate_tuple!(A 0);
+interpolate_tuple!(A 0, B 1);
+interpolate_tuple!(A 0, B 1, C 2);
+interpolate_tuple!(A 0, B 1, C 2, D 3);
+
+
+/// An sRGB color, it displays as a CSS `rgba()` color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rgba {
+    /// Between `0.0` and `255.0`.
+    pub r: f64,
+    /// Between `0.0` and `255.0`.
+    pub g: f64,
+    /// Between `0.0` and `255.0`.
+    pub b: f64,
+    /// Between `0.0` and `1.0`.
+    pub a: f64,
+}
+
+impl Rgba {
+    #[inline]
+    pub fn new(r: f64, g: f64, b: f64, a: f64) -> Self {
+        Self { r, g, b, a }
+    }
+}
+
+impl Interpolate for Rgba {
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        Self {
+            r: self.r.interpolate(&to.r, percentage),
+            g: self.g.interpolate(&to.g, percentage),
+            b: self.b.interpolate(&to.b, percentage),
+            a: self.a.interpolate(&to.a, percentage),
+        }
+    }
+}
+
+impl fmt::Display for Rgba {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Easing can overshoot, so this clamps to valid values
+        write!(f, "rgba({}, {}, {}, {})",
+            self.r.clamp(0.0, 255.0).round(),
+            self.g.clamp(0.0, 255.0).round(),
+            self.b.clamp(0.0, 255.0).round(),
+            self.a.clamp(0.0, 1.0))
+    }
+}
+
+
+/// A color in the HSL color space, it displays as a CSS `hsla()` color.
+///
+/// The hue is interpolated in the shortest direction around the color wheel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hsla {
+    /// In degrees.
+    pub h: f64,
+    /// Between `0.0` and `100.0`.
+    pub s: f64,
+    /// Between `0.0` and `100.0`.
+    pub l: f64,
+    /// Between `0.0` and `1.0`.
+    pub a: f64,
+}
+
+impl Hsla {
+    #[inline]
+    pub fn new(h: f64, s: f64, l: f64, a: f64) -> Self {
+        Self { h, s, l, a }
+    }
+}
+
+impl Interpolate for Hsla {
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        let from = self.h.rem_euclid(360.0);
+        let mut diff = to.h.rem_euclid(360.0) - from;
+
+        if diff > 180.0 {
+            diff -= 360.0;
+
+        } else if diff < -180.0 {
+            diff += 360.0;
+        }
+
+        Self {
+            h: (from + diff * percentage.into_f64()).rem_euclid(360.0),
+            s: self.s.interpolate(&to.s, percentage),
+            l: self.l.interpolate(&to.l, percentage),
+            a: self.a.interpolate(&to.a, percentage),
+        }
+    }
+}
+
+impl fmt::Display for Hsla {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "hsla({}, {}%, {}%, {})",
+            self.h,
+            self.s.clamp(0.0, 100.0),
+            self.l.clamp(0.0, 100.0),
+            self.a.clamp(0.0, 1.0))
+    }
+}
+
+
+/// One function in a [`Transforms`] list.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// In `px`.
+    Translate(f64, f64),
+    Scale(f64, f64),
+    /// In degrees.
+    Rotate(f64),
+    /// In degrees.
+    Skew(f64, f64),
+}
+
+impl Transform {
+    fn identity(&self) -> Self {
+        match self {
+            Self::Translate(_, _) => Self::Translate(0.0, 0.0),
+            Self::Scale(_, _) => Self::Scale(1.0, 1.0),
+            Self::Rotate(_) => Self::Rotate(0.0),
+            Self::Skew(_, _) => Self::Skew(0.0, 0.0),
+        }
+    }
+
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Option<Self> {
+        match (self, to) {
+            (Self::Translate(x1, y1), Self::Translate(x2, y2)) => Some(Self::Translate(x1.interpolate(x2, percentage), y1.interpolate(y2, percentage))),
+            (Self::Scale(x1, y1), Self::Scale(x2, y2)) => Some(Self::Scale(x1.interpolate(x2, percentage), y1.interpolate(y2, percentage))),
+            (Self::Rotate(a1), Self::Rotate(a2)) => Some(Self::Rotate(a1.interpolate(a2, percentage))),
+            (Self::Skew(x1, y1), Self::Skew(x2, y2)) => Some(Self::Skew(x1.interpolate(x2, percentage), y1.interpolate(y2, percentage))),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Translate(x, y) => write!(f, "translate({}px, {}px)", x, y),
+            Self::Scale(x, y) => write!(f, "scale({}, {})", x, y),
+            Self::Rotate(a) => write!(f, "rotate({}deg)", a),
+            Self::Skew(x, y) => write!(f, "skew({}deg, {}deg)", x, y),
+        }
+    }
+}
+
+/// A list of transforms, it displays as a CSS `transform` value.
+///
+/// Like in CSS, the lists are interpolated function by function. If one list is shorter, it is
+/// extended with identity functions. If the functions don't match, the list switches halfway through.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Transforms(pub Vec<Transform>);
+
+impl Interpolate for Transforms {
+    fn interpolate(&self, to: &Self, percentage: Percentage) -> Self {
+        let len = self.0.len().max(to.0.len());
+
+        let interpolated = (0..len).map(|index| {
+            match (self.0.get(index), to.0.get(index)) {
+                (Some(from), Some(to)) => from.interpolate(to, percentage),
+                (Some(from), None) => from.interpolate(&from.identity(), percentage),
+                (None, Some(to)) => to.identity().interpolate(to, percentage),
+                (None, None) => unreachable!(),
+            }
+        }).collect::<Option<Vec<Transform>>>();
+
+        match interpolated {
+            Some(interpolated) => Self(interpolated),
+            None => if percentage.into_f64() < 0.5 { self.clone() } else { to.clone() },
+        }
+    }
+}
+
+impl fmt::Display for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return f.write_str("none");
+        }
+
+        for (index, transform) in self.0.iter().enumerate() {
+            if index != 0 {
+                f.write_str(" ")?;
+            }
+
+            write!(f, "{}", transform)?;
+        }
+
+        Ok(())
+    }
+}
+
+
+struct TweenState<A> {
+    from: A,
+    to: A,
+    easing: fn(Percentage) -> Percentage,
+    next_easing: fn(Percentage) -> Percentage,
+}
+
+impl<A> TweenState<A> where A: Interpolate {
+    #[inline]
+    fn value(&self, percentage: Percentage) -> A {
+        self.from.interpolate(&self.to, (self.easing)(percentage))
+    }
+}
+
+/// Animates between two values of any [`Interpolate`] type.
+///
+/// ```rust
+/// let color = Tween::new(300.0, Rgba::new(0.0, 0.0, 0.0, 1.0));
+///
+/// html!("div", {
+///     .style_signal("color", color.signal().map(|color| color.to_string()))
+/// })
+///
+/// color.animate_to(Rgba::new(255.0, 0.0, 0.0, 1.0));
+/// ```
+pub struct Tween<A> {
+    animation: MutableAnimation,
+    state: Arc<Mutex<TweenState<A>>>,
+}
+
+impl<A> Clone for Tween<A> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            animation: self.animation.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<A> fmt::Debug for Tween<A> where A: fmt::Debug {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap_throw();
+
+        fmt.debug_struct("Tween")
+            .field("animation", &self.animation)
+            .field("from", &state.from)
+            .field("to", &state.to)
+            .finish()
+    }
+}
+
+impl<A> Tween<A> where A: Interpolate + Clone {
+    /// Every animation takes `duration` milliseconds, and uses [`easing::linear`].
+    #[inline]
+    pub fn new(duration: f64, initial: A) -> Self {
+        Self::new_with_easing(duration, initial, easing::linear)
+    }
+
+    pub fn new_with_easing(duration: f64, initial: A, easing: fn(Percentage) -> Percentage) -> Self {
+        Self {
+            animation: MutableAnimation::new_with_initial(duration, Percentage::END),
+            state: Arc::new(Mutex::new(TweenState {
+                from: initial.clone(),
+                to: initial,
+                easing,
+                next_easing: easing,
+            })),
+        }
+    }
+
+    #[inline]
+    pub fn set_duration(&self, duration: f64) {
+        self.animation.set_duration(duration);
+    }
+
+    /// Overrides the global [`ReducedMotion`] policy for this tween.
+    #[inline]
+    pub fn set_reduced_motion(&self, policy: ReducedMotion) {
+        self.animation.set_reduced_motion(policy);
+    }
+
+    /// The easing is used starting with the next animation.
+    #[inline]
+    pub fn set_easing(&self, easing: fn(Percentage) -> Percentage) {
+        self.state.lock().unwrap_throw().next_easing = easing;
+    }
+
+    /// Starts animating from the current value to `value`.
+    pub fn animate_to(&self, value: A) {
+        {
+            let mut state = self.state.lock().unwrap_throw();
+            state.from = state.value(self.animation.current_percentage());
+            state.to = value;
+            state.easing = state.next_easing;
+        }
+
+        self.animation.jump_to(Percentage::START);
+        self.animation.animate_to(Percentage::END);
+    }
+
+    pub fn jump_to(&self, value: A) {
+        {
+            let mut state = self.state.lock().unwrap_throw();
+            state.from = value.clone();
+            state.to = value;
+            state.easing = state.next_easing;
+        }
+
+        // When the tween isn't animating it is already at the end, so this makes sure that the signal changes
+        self.animation.jump_to(Percentage::START);
+        self.animation.jump_to(Percentage::END);
+    }
+
+    #[inline]
+    pub fn pause(&self) {
+        self.animation.pause();
+    }
+
+    #[inline]
+    pub fn play(&self) {
+        self.animation.play();
+    }
+
+    pub fn get(&self) -> A {
+        self.state.lock().unwrap_throw().value(self.animation.current_percentage())
+    }
+
+    #[inline]
+    pub fn signal(&self) -> TweenSignal<A> {
+        TweenSignal {
+            signal: self.animation.signal(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+
+#[must_use = "Signals do nothing unless polled"]
+pub struct TweenSignal<A> {
+    signal: MutableAnimationSignal,
+    state: Arc<Mutex<TweenState<A>>>,
+}
+
+impl<A> fmt::Debug for TweenSignal<A> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("TweenSignal")
+            .field("signal", &self.signal)
+            .finish()
+    }
+}
+
+impl<A> Signal for TweenSignal<A> where A: Interpolate {
+    type Item = A;
+
+    #[inline]
+    fn poll_change(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let state = self.state.clone();
+
+        self.signal.poll_change_unpin(cx).map(|percentage| {
+            percentage.map(|percentage| state.lock().unwrap_throw().value(percentage))
+        })
+    }
+}
+
+
+/// A virtual clock, which makes animations deterministic.
+///
+/// ```rust
+/// testing::use_virtual_clock();
+///
+/// let animation = MutableAnimation::new(1000.0);
+/// animation.animate_to(Percentage::new(1.0));
+///
+/// // The first frame is the start of the animation
+/// testing::advance(0.0);
+/// testing::advance(250.0);
+///
+/// assert_eq!(animation.current_percentage(), Percentage::new(0.25));
+/// ```
+///
+/// The virtual clock also runs the futures which are spawned by dominator,
+/// so it doesn't need a browser.
+///
+/// This requires the `testing` feature.
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+    use std::task::Context;
+    use futures_util::task::{ArcWake, waker};
+    use wasm_bindgen::UnwrapThrowExt;
+
+    use super::{FrameSource, set_frame_source};
+
+
+    type Callback = Box<dyn FnMut(f64)>;
+
+    struct VirtualFrames {
+        running: Cell<bool>,
+        callback: RefCell<Option<Callback>>,
+    }
+
+    impl FrameSource for Rc<VirtualFrames> {
+        fn start(&self, callback: Callback) {
+            self.running.set(true);
+            *self.callback.borrow_mut() = Some(callback);
+        }
+
+        fn stop(&self) {
+            self.running.set(false);
+            *self.callback.borrow_mut() = None;
+        }
+    }
+
+
+    type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+    struct TaskWaker {
+        id: usize,
+        ready: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl ArcWake for TaskWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.ready.lock().unwrap_throw().push(arc_self.id);
+        }
+    }
+
+
+    thread_local! {
+        static TIME: Cell<f64> = const { Cell::new(0.0) };
+        static FRAMES: RefCell<Option<Rc<VirtualFrames>>> = const { RefCell::new(None) };
+        static TASKS: RefCell<Vec<Option<Task>>> = const { RefCell::new(vec![]) };
+        // The slots of finished tasks, which are reused by new tasks
+        static FREE: RefCell<Vec<usize>> = const { RefCell::new(vec![]) };
+        static READY: Arc<Mutex<Vec<usize>>> = Arc::new(Mutex::new(vec![]));
+    }
+
+
+    #[inline]
+    pub(crate) fn is_enabled() -> bool {
+        FRAMES.with(|frames| frames.borrow().is_some())
+    }
+
+    pub(crate) fn spawn<F>(future: F) where F: Future<Output = ()> + 'static {
+        let future: Task = Box::pin(future);
+
+        let id = TASKS.with(|tasks| {
+            let mut tasks = tasks.borrow_mut();
+
+            match FREE.with(|free| free.borrow_mut().pop()) {
+                Some(id) => {
+                    tasks[id] = Some(future);
+                    id
+                },
+                None => {
+                    tasks.push(Some(future));
+                    tasks.len() - 1
+                },
+            }
+        });
+
+        READY.with(|ready| ready.lock().unwrap_throw().push(id));
+    }
+
+    fn run_tasks() {
+        let ready = READY.with(|ready| ready.clone());
+
+        loop {
+            let ids = std::mem::take(&mut *ready.lock().unwrap_throw());
+
+            if ids.is_empty() {
+                break;
+            }
+
+            for id in ids {
+                // The task is removed while it is polled, because it might spawn other tasks
+                let task = TASKS.with(|tasks| tasks.borrow_mut()[id].take());
+
+                if let Some(mut task) = task {
+                    let waker = waker(Arc::new(TaskWaker { id, ready: ready.clone() }));
+
+                    // Old wakers might wake a task which reused the slot, but spurious polls are fine
+                    if task.as_mut().poll(&mut Context::from_waker(&waker)).is_pending() {
+                        TASKS.with(|tasks| tasks.borrow_mut()[id] = Some(task));
+
+                    } else {
+                        FREE.with(|free| free.borrow_mut().push(id));
+                    }
+                }
+            }
+        }
+    }
+
+
+    /// Switches the current thread to the virtual clock, the time starts at `0`.
+    ///
+    /// Frames only happen when [`advance`] is called.
+    pub fn use_virtual_clock() {
+        let frames = Rc::new(VirtualFrames {
+            running: Cell::new(false),
+            callback: RefCell::new(None),
+        });
+
+        FRAMES.with(|x| *x.borrow_mut() = Some(frames.clone()));
+        TIME.with(|time| time.set(0.0));
+
+        set_frame_source(frames);
+    }
+
+    /// Moves the virtual clock forward by `ms` milliseconds, and then runs one frame.
+    ///
+    /// All of the futures which are ready are run before and after the frame.
+    pub fn advance(ms: f64) {
+        debug_assert!(ms >= 0.0);
+
+        let frames = FRAMES.with(|frames| frames.borrow().clone()).expect_throw("use_virtual_clock must be called first");
+
+        let time = TIME.with(|time| {
+            let value = time.get() + ms;
+            time.set(value);
+            value
+        });
+
+        run_tasks();
+
+        let callback = frames.callback.borrow_mut().take();
+
+        if let Some(mut callback) = callback {
+            callback(time);
+
+            // The callback might have stopped the frames
+            if frames.running.get() && frames.callback.borrow().is_none() {
+                *frames.callback.borrow_mut() = Some(callback);
+            }
+        }
+
+        run_tasks();
+    }
+
+    /// The current time of the virtual clock.
+    #[inline]
+    pub fn now() -> f64 {
+        TIME.with(|time| time.get())
+    }
+
+    /// Simulates the user's [`prefers-reduced-motion`](super::prefers_reduced_motion) preference.
+    #[inline]
+    pub fn set_prefers_reduced_motion(value: bool) {
+        super::prefers_reduced_motion_mutable().set_neq(value);
+    }
+}
+
+
+pub mod easing {
+    use std::f64::consts::PI;
+    use std::fmt;
+    use std::str::FromStr;
+    use super::Percentage;
+
+    #[inline]
+    pub fn linear(p: Percentage) -> Percentage {
+        p
+    }
+
+    // TODO should this use map rather than map_unchecked ?
+    #[inline]
+    pub fn powi(p: Percentage, n: i32) -> Percentage {
+        p.map_unchecked(|p| p.powi(n))
+    }
+
+    #[inline]
+    pub fn quad(p: Percentage) -> Percentage {
+        powi(p, 2)
+    }
+
+    #[inline]
+    pub fn cubic(p: Percentage) -> Percentage {
+        powi(p, 3)
+    }
+
+    #[inline]
+    pub fn quart(p: Percentage) -> Percentage {
+        powi(p, 4)
+    }
+
+    #[inline]
+    pub fn quint(p: Percentage) -> Percentage {
+        powi(p, 5)
+    }
+
+    #[inline]
+    pub fn sine(p: Percentage) -> Percentage {
+        p.map_unchecked(|p| 1.0 - (p * PI / 2.0).cos())
+    }
+
+    pub fn expo(p: Percentage) -> Percentage {
+        p.map_unchecked(|p| {
+            if p == 0.0 {
+                0.0
+
+            } else {
+                2.0_f64.powf(10.0 * p - 10.0)
+            }
+        })
+    }
+
+    #[inline]
+    pub fn circ(p: Percentage) -> Percentage {
+        p.map_unchecked(|p| 1.0 - (1.0 - p * p).sqrt())
+    }
+
+    /// Moves backwards slightly before moving forwards, so it goes below `0.0`.
+    pub fn back(p: Percentage) -> Percentage {
+        const C1: f64 = 1.70158;
+        const C3: f64 = C1 + 1.0;
+
+        p.map_unchecked(|p| C3 * p * p * p - C1 * p * p)
+    }
+
+    /// Oscillates like a spring, so it goes below `0.0`.
+    pub fn elastic(p: Percentage) -> Percentage {
+        const C4: f64 = (2.0 * PI) / 3.0;
+
+        p.map_unchecked(|p| {
+            if p == 0.0 || p == 1.0 {
+                p
+
+            } else {
+                -(2.0_f64.powf(10.0 * p - 10.0)) * ((p * 10.0 - 10.75) * C4).sin()
+            }
+        })
+    }
+
+    fn bounce_out(p: f64) -> f64 {
+        const N1: f64 = 7.5625;
+        const D1: f64 = 2.75;
+
+        if p < 1.0 / D1 {
+            N1 * p * p
+
+        } else if p < 2.0 / D1 {
+            let p = p - 1.5 / D1;
+            N1 * p * p + 0.75
+
+        } else if p < 2.5 / D1 {
+            let p = p - 2.25 / D1;
+            N1 * p * p + 0.9375
+
+        } else {
+            let p = p - 2.625 / D1;
+            N1 * p * p + 0.984375
+        }
+    }
+
+    /// Bounces like a ball. Like the other easings this is the ease-in version, so it bounces at the start
+    /// and then accelerates towards `1.0`.
+    ///
+    /// Use it with [`out`] to bounce at the end, e.g. when something drops into place.
+    #[inline]
+    pub fn bounce(p: Percentage) -> Percentage {
+        p.map_unchecked(|p| 1.0 - bounce_out(1.0 - p))
+    }
+
+    #[inline]
+    pub fn out<F>(p: Percentage, f: F) -> Percentage where F: FnOnce(Percentage) -> Percentage {
+        f(p.invert()).invert()
+    }
+
+    pub fn in_out<F>(p: Percentage, f: F) -> Percentage where F: FnOnce(Percentage) -> Percentage {
+        p.map_unchecked(|p| {
+            if p <= 0.5 {
+                f(Percentage::new_unchecked(p * 2.0)).into_f64() / 2.0
+
+            } else {
+                1.0 - (f(Percentage::new_unchecked((1.0 - p) * 2.0)).into_f64() / 2.0)
+            }
+        })
+    }
+
+
+    /*pub struct Point {
+        pub x: f64,
+        pub y: f64,
+    }*/
+
+    /*impl Point {
+        fn range_inclusive(percentage: f64, from: &Self, to: &Self) -> Self {
+            Point {
+                x: range_inclusive(percentage, from.x, to.x),
+                y: range_inclusive(percentage, from.y, to.y),
+            }
+        }
+    }*/
+
+    /*#[inline]
+    fn get_values(start: f64, end: f64) -> (f64, f64, f64) {
+        let start = 3.0 * start;
+        let end = 3.0 * end;
+        let a = 1.0 - end + start;
+        let b = end - (2.0 * start);
+        (a, b, start)
+    }
+
+    fn interpolate(p: f64, start: f64, end: f64) -> f64 {
+        let (a, b, c) = get_values(start, end);
+        ((a * p + b) * p + c) * p
+    }
+
+    fn get_slope(p: f64, start: f64, end: f64) -> f64 {
+        let (a, b, c) = get_values(start, end);
+        (3.0 * a * p * p) + (2.0 * b * p) + c
+    }*/
+
+
+    const EPSILON: f64 = 1e-6;
+
+    /// The same as the CSS [`cubic-bezier()`](https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function/cubic-bezier) function.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct CubicBezier {
+        ax: f64,
+        bx: f64,
+        cx: f64,
+
+        ay: f64,
+        by: f64,
+        cy: f64,
+    }
+
+    /// The CSS `ease` easing.
+    pub const EASE: CubicBezier = CubicBezier::new(0.25, 0.1, 0.25, 1.0);
+
+    /// The CSS `ease-in` easing.
+    pub const EASE_IN: CubicBezier = CubicBezier::new(0.42, 0.0, 1.0, 1.0);
+
+    /// The CSS `ease-out` easing.
+    pub const EASE_OUT: CubicBezier = CubicBezier::new(0.0, 0.0, 0.58, 1.0);
+
+    /// The CSS `ease-in-out` easing.
+    pub const EASE_IN_OUT: CubicBezier = CubicBezier::new(0.42, 0.0, 0.58, 1.0);
+
+    impl CubicBezier {
+        /// The `x` values must be between `0.0` and `1.0`, but the `y` values can be outside of that range.
+        pub const fn new(x1: f64, y1: f64, x2: f64, y2: f64) -> Self {
+            assert!(x1 >= 0.0 && x1 <= 1.0);
+            assert!(x2 >= 0.0 && x2 <= 1.0);
+
+            let cx = 3.0 * x1;
+            let bx = 3.0 * (x2 - x1) - cx;
+            let ax = 1.0 - cx - bx;
+
+            let cy = 3.0 * y1;
+            let by = 3.0 * (y2 - y1) - cy;
             let ay = 1.0 - cy - by;
 
             Self { ax, bx, cx, ay, by, cy }
@@ -1072,6 +3182,187 @@ pub mod easing {
             }
         }*/
     }
+
+
+    /// Where the jumps happen for [`Steps`].
+    #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+    pub enum StepPosition {
+        /// The first jump happens at the start, this is the same as CSS `start`.
+        JumpStart,
+
+        /// The last jump happens at the end, this is the same as CSS `end`.
+        JumpEnd,
+
+        /// There is no jump at the start or the end.
+        JumpNone,
+
+        /// There is a jump at both the start and the end.
+        JumpBoth,
+    }
+
+    /// The same as the CSS [`steps()`](https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function/steps) function.
+    #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+    pub struct Steps {
+        steps: u32,
+        position: StepPosition,
+    }
+
+    impl Steps {
+        pub const fn new(steps: u32, position: StepPosition) -> Self {
+            match position {
+                StepPosition::JumpNone => assert!(steps >= 2),
+                _ => assert!(steps >= 1),
+            }
+
+            Self { steps, position }
+        }
+
+        pub fn easing(&self, p: Percentage) -> Percentage {
+            p.map_unchecked(|p| {
+                let steps = self.steps as f64;
+
+                let mut step = (p * steps).floor();
+
+                if let StepPosition::JumpStart | StepPosition::JumpBoth = self.position {
+                    step += 1.0;
+                }
+
+                let jumps = match self.position {
+                    StepPosition::JumpStart | StepPosition::JumpEnd => steps,
+                    StepPosition::JumpNone => steps - 1.0,
+                    StepPosition::JumpBoth => steps + 1.0,
+                };
+
+                if p >= 0.0 && step < 0.0 {
+                    step = 0.0;
+                }
+
+                if p <= 1.0 && step > jumps {
+                    step = jumps;
+                }
+
+                step / jumps
+            })
+        }
+    }
+
+
+    /// An easing which can be parsed from a CSS [`<easing-function>`](https://developer.mozilla.org/en-US/docs/Web/CSS/easing-function):
+    ///
+    /// ```rust
+    /// let easing: Easing = "cubic-bezier(0.1, 0.7, 1.0, 0.1)".parse().unwrap();
+    ///
+    /// let p = easing.easing(Percentage::new(0.5));
+    /// ```
+    ///
+    /// The `linear()` function is not supported.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum Easing {
+        Linear,
+        CubicBezier(CubicBezier),
+        Steps(Steps),
+    }
+
+    impl Easing {
+        pub fn easing(&self, p: Percentage) -> Percentage {
+            match self {
+                Self::Linear => p,
+                Self::CubicBezier(x) => x.easing(p),
+                Self::Steps(x) => x.easing(p),
+            }
+        }
+    }
+
+    impl Default for Easing {
+        #[inline]
+        fn default() -> Self {
+            Self::CubicBezier(EASE)
+        }
+    }
+
+    /// The error when parsing an [`Easing`] fails.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct InvalidEasing(String);
+
+    impl fmt::Display for InvalidEasing {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "invalid easing: {:?}", self.0)
+        }
+    }
+
+    impl std::error::Error for InvalidEasing {}
+
+    // Parses `name(args)` and returns the arguments
+    fn parse_function<'a>(input: &'a str, name: &str) -> Option<Vec<&'a str>> {
+        let args = input.strip_prefix(name)?.trim_start().strip_prefix('(')?.strip_suffix(')')?;
+        Some(args.split(',').map(|arg| arg.trim()).collect())
+    }
+
+    fn parse_cubic_bezier(input: &str) -> Option<CubicBezier> {
+        let args = parse_function(input, "cubic-bezier")?;
+
+        if let [x1, y1, x2, y2] = args.as_slice() {
+            let x1: f64 = x1.parse().ok()?;
+            let y1: f64 = y1.parse().ok()?;
+            let x2: f64 = x2.parse().ok()?;
+            let y2: f64 = y2.parse().ok()?;
+
+            if (0.0..=1.0).contains(&x1) && (0.0..=1.0).contains(&x2) && y1.is_finite() && y2.is_finite() {
+                return Some(CubicBezier::new(x1, y1, x2, y2));
+            }
+        }
+
+        None
+    }
+
+    fn parse_steps(input: &str) -> Option<Steps> {
+        let args = parse_function(input, "steps")?;
+
+        let (steps, position) = match args.as_slice() {
+            [steps] => (steps, StepPosition::JumpEnd),
+            [steps, position] => (steps, match *position {
+                "jump-start" | "start" => StepPosition::JumpStart,
+                "jump-end" | "end" => StepPosition::JumpEnd,
+                "jump-none" => StepPosition::JumpNone,
+                "jump-both" => StepPosition::JumpBoth,
+                _ => return None,
+            }),
+            _ => return None,
+        };
+
+        let steps: u32 = steps.parse().ok()?;
+
+        let min = if let StepPosition::JumpNone = position { 2 } else { 1 };
+
+        if steps >= min {
+            Some(Steps::new(steps, position))
+
+        } else {
+            None
+        }
+    }
+
+    impl FromStr for Easing {
+        type Err = InvalidEasing;
+
+        fn from_str(input: &str) -> Result<Self, Self::Err> {
+            let trimmed = input.trim();
+
+            let easing = match trimmed {
+                "linear" => Some(Self::Linear),
+                "ease" => Some(Self::CubicBezier(EASE)),
+                "ease-in" => Some(Self::CubicBezier(EASE_IN)),
+                "ease-out" => Some(Self::CubicBezier(EASE_OUT)),
+                "ease-in-out" => Some(Self::CubicBezier(EASE_IN_OUT)),
+                "step-start" => Some(Self::Steps(Steps::new(1, StepPosition::JumpStart))),
+                "step-end" => Some(Self::Steps(Steps::new(1, StepPosition::JumpEnd))),
+                _ => parse_cubic_bezier(trimmed).map(Self::CubicBezier)
+                    .or_else(|| parse_steps(trimmed).map(Self::Steps)),
+            };
+
+            easing.ok_or_else(|| InvalidEasing(input.to_string()))
+        }
+    }
 }
 
 
@@ -1081,3 +3372,476 @@ pub mod easing {
     Percentage::new(0.66),
     Percentage::new(0.66),
 )*/
+
+
+#[cfg(test)]
+mod tests {
+    use super::{MutableAnimation, MutableSpring, Percentage, testing, Interpolate, Rgba, Hsla, Transform, Transforms, Tween, easing, ReducedMotion, set_reduced_motion, AnimationInterrupted};
+    use super::{Timeline, TimelineStep, toggle_rate, AnimatedSignalVec};
+    use discard::Discard;
+    use std::sync::{Arc, Mutex};
+    use futures_util::future::FutureExt;
+    use futures_util::stream::StreamExt;
+    use futures_signals::signal_vec::{MutableVec, SignalVecExt, VecDiff};
+    use futures_signals::signal::SignalExt;
+
+    #[test]
+    fn animate_to() {
+        testing::use_virtual_clock();
+
+        let animation = MutableAnimation::new(1000.0);
+        animation.animate_to(Percentage::new(1.0));
+
+        assert_eq!(animation.current_percentage(), Percentage::new(0.0));
+
+        // The first frame is the start of the animation
+        testing::advance(16.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.0));
+
+        testing::advance(250.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.25));
+
+        testing::advance(250.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.5));
+
+        // Going back only takes half of the duration
+        animation.animate_to(Percentage::new(0.0));
+
+        testing::advance(16.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.5));
+
+        testing::advance(250.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.25));
+
+        testing::advance(300.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.0));
+
+        testing::advance(100.0);
+        assert_eq!(animation.current_percentage(), Percentage::new(0.0));
+        assert_eq!(testing::now(), 1182.0);
+    }
+
+    #[test]
+    fn spring() {
+        testing::use_virtual_clock();
+
+        let spring = MutableSpring::new(0.0);
+        spring.animate_to(100.0);
+
+        testing::advance(16.0);
+
+        for _ in 0..10 {
+            testing::advance(16.0);
+        }
+
+        let value = spring.current_value();
+        assert!(value > 0.0 && value < 100.0);
+        assert!(!spring.is_settled());
+
+        // Retargeting keeps the velocity
+        let velocity = spring.velocity();
+        spring.animate_to(50.0);
+        assert_eq!(spring.velocity(), velocity);
+
+        for _ in 0..200 {
+            testing::advance(16.0);
+        }
+
+        assert!(spring.is_settled());
+        assert_eq!(spring.current_value(), 50.0);
+        assert_eq!(spring.velocity(), 0.0);
+    }
+
+    #[test]
+    fn interpolate() {
+        let half = Percentage::new(0.5);
+
+        assert_eq!(10.0.interpolate(&20.0, half), 15.0);
+        assert_eq!((0.0, [10.0, 20.0]).interpolate(&(1.0, [20.0, 40.0]), half), (0.5, [15.0, 30.0]));
+
+        let color = Rgba::new(0.0, 0.0, 0.0, 0.0).interpolate(&Rgba::new(255.0, 100.0, 0.0, 1.0), half);
+        assert_eq!(color.to_string(), "rgba(128, 50, 0, 0.5)");
+
+        // The hue goes the short way around
+        assert_eq!(Hsla::new(350.0, 0.0, 0.0, 1.0).interpolate(&Hsla::new(10.0, 100.0, 50.0, 1.0), half), Hsla::new(0.0, 50.0, 25.0, 1.0));
+
+        let transforms = Transforms(vec![Transform::Translate(10.0, 0.0)]).interpolate(&Transforms(vec![
+            Transform::Translate(20.0, 10.0),
+            Transform::Scale(2.0, 2.0),
+        ]), half);
+
+        assert_eq!(transforms.to_string(), "translate(15px, 5px) scale(1.5, 1.5)");
+
+        // Mismatched functions switch halfway
+        let from = Transforms(vec![Transform::Rotate(90.0)]);
+        let to = Transforms(vec![Transform::Scale(2.0, 2.0)]);
+        assert_eq!(from.interpolate(&to, Percentage::new(0.25)), from);
+        assert_eq!(from.interpolate(&to, Percentage::new(0.75)), to);
+
+        assert_eq!(Transforms::default().to_string(), "none");
+    }
+
+    #[test]
+    fn tween() {
+        testing::use_virtual_clock();
+
+        let tween = Tween::new(1000.0, (0.0, 100.0));
+        assert_eq!(tween.get(), (0.0, 100.0));
+
+        tween.animate_to((100.0, 0.0));
+
+        testing::advance(0.0);
+        testing::advance(250.0);
+        assert_eq!(tween.get(), (25.0, 75.0));
+
+        // Retargeting starts from the current value
+        tween.set_easing(easing::cubic);
+        tween.animate_to((25.0, 175.0));
+
+        testing::advance(0.0);
+        assert_eq!(tween.get(), (25.0, 75.0));
+
+        testing::advance(500.0);
+        assert_eq!(tween.get(), (25.0, 87.5));
+
+        testing::advance(500.0);
+        assert_eq!(tween.get(), (25.0, 175.0));
+    }
+
+    #[test]
+    fn tween_jump_to() {
+        testing::use_virtual_clock();
+
+        let tween = Tween::new(1000.0, 0.0);
+
+        let mut values = tween.signal().to_stream();
+        assert_eq!(values.next().now_or_never(), Some(Some(0.0)));
+        assert_eq!(values.next().now_or_never(), None);
+
+        // The tween isn't animating, so it's already at the end
+        tween.jump_to(50.0);
+        assert_eq!(tween.get(), 50.0);
+        assert_eq!(values.next().now_or_never(), Some(Some(50.0)));
+        assert_eq!(values.next().now_or_never(), None);
+    }
+
+    #[test]
+    fn easings() {
+        use super::easing::{Easing, Steps, StepPosition, EASE_IN_OUT};
+
+        let functions: [fn(Percentage) -> Percentage; 10] = [
+            easing::linear, easing::quad, easing::cubic, easing::quart, easing::quint,
+            easing::sine, easing::expo, easing::circ, easing::back, easing::elastic,
+        ];
+
+        for f in functions.iter() {
+            assert!((f(Percentage::START).into_f64() - 0.0).abs() < 1e-3);
+            assert!((f(Percentage::END).into_f64() - 1.0).abs() < 1e-9);
+        }
+
+        assert_eq!(easing::bounce(Percentage::START), Percentage::START);
+        assert!((easing::bounce(Percentage::END).into_f64() - 1.0).abs() < 1e-9);
+        assert!((easing::bounce(Percentage::new(0.5)).into_f64() - 0.234375).abs() < 1e-9);
+        assert!((easing::out(Percentage::new(0.5), easing::bounce).into_f64() - 0.765625).abs() < 1e-9);
+        assert!(easing::back(Percentage::new(0.2)).into_f64() < 0.0);
+
+        assert_eq!("ease-in-out".parse(), Ok(Easing::CubicBezier(EASE_IN_OUT)));
+        assert_eq!(" linear ".parse(), Ok(Easing::Linear));
+        assert_eq!("steps(4)".parse(), Ok(Easing::Steps(Steps::new(4, StepPosition::JumpEnd))));
+        assert_eq!("steps(2, jump-none)".parse(), Ok(Easing::Steps(Steps::new(2, StepPosition::JumpNone))));
+        assert_eq!("step-start".parse(), Ok(Easing::Steps(Steps::new(1, StepPosition::JumpStart))));
+
+        assert!("cubic-bezier(1.5, 0, 0, 1)".parse::<Easing>().is_err());
+        assert!("steps(0)".parse::<Easing>().is_err());
+        assert!("steps(1, jump-none)".parse::<Easing>().is_err());
+        assert!("bounce".parse::<Easing>().is_err());
+
+        let overshoot: Easing = "cubic-bezier(0.68, -0.55, 0.265, 1.55)".parse().unwrap();
+        assert!(overshoot.easing(Percentage::new(0.1)).into_f64() < 0.0);
+
+        let steps: Easing = "steps(4, jump-end)".parse().unwrap();
+        assert_eq!(steps.easing(Percentage::new(0.0)), Percentage::new(0.0));
+        assert_eq!(steps.easing(Percentage::new(0.3)), Percentage::new(0.25));
+        assert_eq!(steps.easing(Percentage::new(1.0)), Percentage::new(1.0));
+
+        let steps: Easing = "steps(4, start)".parse().unwrap();
+        assert_eq!(steps.easing(Percentage::new(0.0)), Percentage::new(0.25));
+
+        let steps: Easing = "steps(3, jump-none)".parse().unwrap();
+        assert_eq!(steps.easing(Percentage::new(0.5)), Percentage::new(0.5));
+        assert_eq!(steps.easing(Percentage::new(0.9)), Percentage::new(1.0));
+
+        let steps: Easing = "steps(3, jump-both)".parse().unwrap();
+        assert_eq!(steps.easing(Percentage::new(0.0)), Percentage::new(0.25));
+        assert_eq!(steps.easing(Percentage::new(1.0)), Percentage::new(1.0));
+
+        let ease: Easing = "ease".parse().unwrap();
+        assert!((ease.easing(Percentage::new(0.5)).into_f64() - 0.8024).abs() < 1e-3);
+    }
+
+    #[test]
+    fn reduced_motion() {
+        testing::use_virtual_clock();
+        testing::set_prefers_reduced_motion(true);
+
+        let animation = MutableAnimation::new(1000.0);
+        animation.animate_to(Percentage::END);
+        assert_eq!(animation.current_percentage(), Percentage::END);
+
+        let spring = MutableSpring::new(0.0);
+        spring.animate_to(100.0);
+        assert_eq!(spring.current_value(), 100.0);
+        assert!(spring.is_settled());
+
+        set_reduced_motion(ReducedMotion::Duration(500.0));
+
+        let short = MutableAnimation::new(1000.0);
+        short.animate_to(Percentage::END);
+
+        let essential = MutableAnimation::new(1000.0);
+        essential.set_reduced_motion(ReducedMotion::Animate);
+        essential.animate_to(Percentage::END);
+
+        testing::advance(0.0);
+        testing::advance(250.0);
+        assert_eq!(short.current_percentage(), Percentage::new(0.5));
+        assert_eq!(essential.current_percentage(), Percentage::new(0.25));
+
+        testing::set_prefers_reduced_motion(false);
+
+        let normal = MutableAnimation::new(1000.0);
+        normal.animate_to(Percentage::END);
+
+        testing::advance(0.0);
+        testing::advance(250.0);
+        assert_eq!(normal.current_percentage(), Percentage::new(0.25));
+    }
+
+    #[test]
+    fn animated_map_reduced_motion() {
+        testing::use_virtual_clock();
+        testing::set_prefers_reduced_motion(true);
+
+        fn push(policy: Option<ReducedMotion>) -> Percentage {
+            let values = MutableVec::new();
+
+            let mut map = values.signal_vec().animated_map(1000.0, |value: u32, animation| (value, animation.0));
+
+            if let Some(policy) = policy {
+                map.set_reduced_motion(policy);
+            }
+
+            let mut changes = map.to_stream();
+
+            values.lock_mut().push(5);
+
+            match changes.next().now_or_never() {
+                Some(Some(VecDiff::Push { value: (5, animation) })) => animation.current_percentage(),
+                _ => panic!("expected a Push"),
+            }
+        }
+
+        assert_eq!(push(None), Percentage::END);
+        assert_eq!(push(Some(ReducedMotion::Animate)), Percentage::START);
+    }
+
+    #[test]
+    fn toggle_animation() {
+        // Initially paused at the beginning
+        assert_eq!(toggle_rate(false, Some(0.0), 1.0, false), None);
+        assert_eq!(toggle_rate(true, Some(0.0), 1.0, false), Some(1.0));
+
+        // Reversing in the middle
+        assert_eq!(toggle_rate(false, Some(150.0), 1.0, false), Some(-1.0));
+        assert_eq!(toggle_rate(true, Some(150.0), -1.0, false), Some(1.0));
+
+        // Already at the end
+        assert_eq!(toggle_rate(true, Some(300.0), 1.0, true), None);
+        assert_eq!(toggle_rate(false, Some(300.0), 1.0, true), Some(-1.0));
+
+        // Already back at the beginning
+        assert_eq!(toggle_rate(false, Some(0.0), -1.0, true), None);
+        assert_eq!(toggle_rate(true, Some(0.0), -1.0, true), Some(1.0));
+        assert_eq!(toggle_rate(false, None, 1.0, false), None);
+    }
+
+    #[test]
+    fn timeline_steps() {
+        let a = MutableAnimation::new(0.0);
+        let b = MutableAnimation::new(0.0);
+
+        assert_eq!(TimelineStep::sequence(vec![TimelineStep::animate(&a, 100.0), TimelineStep::delay(20.0)]).duration(), 120.0);
+        assert_eq!(TimelineStep::parallel(vec![TimelineStep::animate(&a, 100.0), TimelineStep::animate(&b, 40.0).with_delay(80.0)]).duration(), 120.0);
+        assert_eq!(TimelineStep::stagger(30.0, vec![TimelineStep::animate(&a, 100.0), TimelineStep::animate(&b, 40.0)]).duration(), 100.0);
+        assert_eq!(TimelineStep::stagger(30.0, vec![TimelineStep::animate(&a, 40.0), TimelineStep::animate(&b, 40.0)]).duration(), 70.0);
+    }
+
+    #[test]
+    fn timeline() {
+        testing::use_virtual_clock();
+
+        let a = MutableAnimation::new(0.0);
+        let b = MutableAnimation::new(0.0);
+        let c = MutableAnimation::new(0.0);
+
+        // a runs from 0 to 100, b from 120 to 160, and c from 160 to 200
+        let timeline = Timeline::new(TimelineStep::sequence(vec![
+            TimelineStep::animate(&a, 100.0),
+            TimelineStep::delay(20.0),
+            TimelineStep::stagger(40.0, vec![
+                TimelineStep::animate(&b, 40.0),
+                TimelineStep::animate(&c, 40.0),
+            ]),
+        ]));
+
+        assert_eq!(timeline.duration(), 200.0);
+        assert!(!timeline.is_playing());
+
+        timeline.play();
+        assert!(timeline.is_playing());
+
+        testing::advance(0.0);
+        testing::advance(50.0);
+        assert_eq!(timeline.position(), 50.0);
+        assert_eq!(a.current_percentage(), Percentage::new(0.5));
+        assert_eq!(b.current_percentage(), Percentage::START);
+
+        testing::advance(90.0);
+        assert_eq!(timeline.position(), 140.0);
+        assert_eq!(a.current_percentage(), Percentage::END);
+        assert_eq!(b.current_percentage(), Percentage::new(0.5));
+        assert_eq!(c.current_percentage(), Percentage::START);
+
+        // Reversing while playing continues from the same position
+        timeline.reverse();
+        assert!(timeline.is_reversed());
+
+        testing::advance(0.0);
+        testing::advance(40.0);
+        assert_eq!(timeline.position(), 100.0);
+        assert_eq!(a.current_percentage(), Percentage::END);
+        assert_eq!(b.current_percentage(), Percentage::START);
+
+        testing::advance(60.0);
+        assert_eq!(timeline.position(), 40.0);
+        assert_eq!(a.current_percentage(), Percentage::new(0.4));
+
+        timeline.pause();
+        testing::advance(20.0);
+        assert_eq!(timeline.position(), 40.0);
+        assert!(!timeline.is_playing());
+
+        timeline.play();
+        testing::advance(0.0);
+        testing::advance(100.0);
+        assert_eq!(timeline.position(), 0.0);
+        assert_eq!(a.current_percentage(), Percentage::START);
+        assert!(!timeline.is_playing());
+
+        timeline.seek_percentage(Percentage::new(0.75));
+        assert_eq!(timeline.position(), 150.0);
+        assert_eq!(a.current_percentage(), Percentage::END);
+        assert_eq!(b.current_percentage(), Percentage::new(0.75));
+        assert_eq!(c.current_percentage(), Percentage::START);
+
+        // Playing forwards from the seeked position
+        timeline.reverse();
+        timeline.play();
+        testing::advance(0.0);
+        testing::advance(30.0);
+        assert_eq!(timeline.position(), 180.0);
+        assert_eq!(b.current_percentage(), Percentage::END);
+        assert_eq!(c.current_percentage(), Percentage::new(0.5));
+
+        testing::advance(100.0);
+        assert_eq!(timeline.position(), 200.0);
+        assert_eq!(c.current_percentage(), Percentage::END);
+        assert!(!timeline.is_playing());
+    }
+
+    #[test]
+    fn animate_to_future() {
+        testing::use_virtual_clock();
+
+        let animation = MutableAnimation::new(1000.0);
+
+        let finished = Arc::new(Mutex::new(vec![]));
+
+        let handle = animation.on_finished({
+            let finished = finished.clone();
+            move |end| finished.lock().unwrap().push(end)
+        });
+
+        let mut first = Box::pin(animation.animate_to_future(Percentage::END));
+        assert!(animation.is_playing());
+
+        testing::advance(0.0);
+        testing::advance(500.0);
+        assert_eq!((&mut first).now_or_never(), None);
+
+        let mut second = Box::pin(animation.animate_to_future(Percentage::START));
+        assert_eq!(first.now_or_never(), Some(Err(AnimationInterrupted)));
+
+        testing::advance(0.0);
+        testing::advance(500.0);
+        assert_eq!(second.as_mut().now_or_never(), Some(Ok(())));
+        assert!(!animation.is_playing());
+        assert_eq!(*finished.lock().unwrap(), vec![Percentage::START]);
+
+        // Already at the end
+        assert_eq!(animation.animate_to_future(Percentage::START).now_or_never(), Some(Ok(())));
+
+        let third = animation.animate_to_future(Percentage::END);
+        animation.jump_to(Percentage::new(0.5));
+        assert_eq!(third.now_or_never(), Some(Err(AnimationInterrupted)));
+        assert_eq!(*finished.lock().unwrap(), vec![Percentage::START]);
+
+        handle.discard();
+
+        animation.animate_to(Percentage::END);
+        testing::advance(0.0);
+        testing::advance(500.0);
+        assert!(!animation.is_playing());
+        assert_eq!(*finished.lock().unwrap(), vec![Percentage::START]);
+    }
+
+    #[test]
+    fn on_finished() {
+        testing::use_virtual_clock();
+
+        let animation = MutableAnimation::new(0.0);
+
+        let finished = Arc::new(Mutex::new(vec![]));
+
+        let first = animation.on_finished({
+            let finished = finished.clone();
+            let animation = animation.clone();
+
+            move |end| {
+                finished.lock().unwrap().push(end);
+
+                // This immediately finishes while the callbacks are running
+                if end == Percentage::END {
+                    animation.animate_to(Percentage::START);
+                }
+            }
+        });
+
+        let second = animation.on_finished({
+            let finished = finished.clone();
+            move |end| finished.lock().unwrap().push(end)
+        });
+
+        animation.animate_to(Percentage::END);
+        assert_eq!(*finished.lock().unwrap(), vec![Percentage::END, Percentage::END, Percentage::START, Percentage::START]);
+
+        // This also breaks the cycle between the animation and the first callback
+        first.discard();
+        finished.lock().unwrap().clear();
+
+        animation.animate_to(Percentage::END);
+        assert_eq!(*finished.lock().unwrap(), vec![Percentage::END]);
+
+        second.discard();
+    }
+}
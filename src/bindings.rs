@@ -1,7 +1,7 @@
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::{JsCast, intern};
-use js_sys::Reflect;
-use web_sys::{HtmlElement, Element, Node, Window, History, Document, Text, Comment, DomTokenList, CssStyleSheet, CssStyleDeclaration, HtmlStyleElement, CssRule};
+use js_sys::{Reflect, Array};
+use web_sys::{HtmlElement, Element, Node, Window, History, Document, Text, Comment, DomTokenList, CssStyleSheet, CssStyleDeclaration, HtmlStyleElement, CssRule, CssRuleList, CssStyleRule, CssGroupingRule, CssKeyframeRule, CssKeyframesRule, CssFontFaceRule, ShadowRoot};
 use crate::utils::UnwrapJsExt;
 
 
@@ -33,19 +33,174 @@ pub(crate) fn current_url() -> String {
 }
 
 #[track_caller]
-pub(crate) fn go_to_url(url: &str) {
+pub(crate) fn parse_url(url: &str) -> web_sys::Url {
+    web_sys::Url::new(url).unwrap_js()
+}
+
+#[track_caller]
+pub(crate) fn url_path(url: &str) -> String {
+    parse_url(url).pathname()
+}
+
+#[track_caller]
+pub(crate) fn resolve_url(url: &str, base: &str) -> String {
+    web_sys::Url::new_with_base(url, base).unwrap_js().href()
+}
+
+pub(crate) fn prevent_unload(event: &web_sys::Event) {
+    event.prevent_default();
+    // Older browsers only show the dialog if returnValue is set
+    event.unchecked_ref::<web_sys::BeforeUnloadEvent>().set_return_value("unsaved");
+}
+
+#[track_caller]
+pub(crate) fn go_to_url(url: &str, state: &JsValue) {
+    HISTORY.with(|h| {
+        h.push_state_with_url(state, "", Some(url)).unwrap_js();
+    });
+}
+
+#[track_caller]
+pub(crate) fn replace_url(url: &str, state: &JsValue) {
+    HISTORY.with(|h| {
+        h.replace_state_with_url(state, "", Some(url)).unwrap_js();
+    });
+}
+
+#[track_caller]
+pub(crate) fn history_state() -> JsValue {
+    HISTORY.with(|h| h.state().unwrap_js())
+}
+
+pub(crate) fn create_history_state(key: Option<f64>, index: Option<f64>, payload: &JsValue) -> JsValue {
+    let has_payload = !payload.is_null() && !payload.is_undefined();
+
+    if key.is_none() && index.is_none() && !has_payload {
+        return JsValue::NULL;
+    }
+
+    let state = js_sys::Object::new();
+
+    if let Some(key) = key {
+        set_property(&state, "dominatorKey", &JsValue::from(key));
+    }
+
+    if let Some(index) = index {
+        set_property(&state, "dominatorIndex", &JsValue::from(index));
+    }
+
+    if has_payload {
+        set_property(&state, "dominatorState", payload);
+    }
+
+    state.into()
+}
+
+pub(crate) fn history_state_payload(state: &JsValue) -> JsValue {
+    if state.is_object() {
+        match Reflect::get(state, &JsValue::from("dominatorState")) {
+            Ok(payload) if !payload.is_undefined() => payload,
+            _ => JsValue::NULL,
+        }
+
+    } else {
+        JsValue::NULL
+    }
+}
+
+pub(crate) fn history_state_key(state: &JsValue) -> Option<f64> {
+    if state.is_object() {
+        Reflect::get(state, &JsValue::from("dominatorKey")).ok()?.as_f64()
+
+    } else {
+        None
+    }
+}
+
+pub(crate) fn history_state_index(state: &JsValue) -> Option<f64> {
+    if state.is_object() {
+        Reflect::get(state, &JsValue::from("dominatorIndex")).ok()?.as_f64()
+
+    } else {
+        None
+    }
+}
+
+#[track_caller]
+pub(crate) fn go(delta: i32) {
     HISTORY.with(|h| {
-        h.push_state_with_url(&JsValue::NULL, "", Some(url)).unwrap_js();
+        h.go_with_delta(delta).unwrap_js();
+    });
+}
+
+pub(crate) fn session_storage_get(key: &str) -> Option<String> {
+    WINDOW.with(|w| w.session_storage().ok()??.get_item(key).ok()?)
+}
+
+pub(crate) fn session_storage_set(key: &str, value: &str) {
+    WINDOW.with(|w| {
+        // sessionStorage can be disabled or full, in that case the value isn't saved
+        if let Ok(Some(storage)) = w.session_storage() {
+            let _ = storage.set_item(key, value);
+        }
     });
 }
 
 #[track_caller]
-pub(crate) fn replace_url(url: &str) {
+pub(crate) fn set_manual_scroll_restoration() {
     HISTORY.with(|h| {
-        h.replace_state_with_url(&JsValue::NULL, "", Some(url)).unwrap_js();
+        h.set_scroll_restoration(web_sys::ScrollRestoration::Manual).unwrap_js();
     });
 }
 
+#[track_caller]
+pub(crate) fn scroll_position() -> (f64, f64) {
+    WINDOW.with(|w| (w.scroll_x().unwrap_js(), w.scroll_y().unwrap_js()))
+}
+
+pub(crate) fn scroll_to(x: f64, y: f64) {
+    WINDOW.with(|w| w.scroll_to_with_x_and_y(x, y));
+}
+
+/// Returns `false` if the element doesn't exist.
+pub(crate) fn scroll_to_id(id: &str) -> bool {
+    DOCUMENT.with(|d| {
+        if let Some(element) = d.get_element_by_id(id) {
+            element.scroll_into_view();
+            true
+
+        } else {
+            false
+        }
+    })
+}
+
+pub(crate) fn now() -> f64 {
+    js_sys::Date::now()
+}
+
+#[track_caller]
+pub(crate) fn next_frame<F>(f: F) where F: FnOnce() + 'static {
+    let callback = Closure::once_into_js(f);
+
+    WINDOW.with(|w| {
+        w.request_animation_frame(callback.unchecked_ref()).unwrap_js();
+    });
+}
+
+// TODO use web-sys once Element.animate is stable
+#[wasm_bindgen]
+extern "C" {
+    type AnimatableElement;
+
+    #[wasm_bindgen(method)]
+    fn animate(this: &AnimatableElement, keyframes: &Array, options: &JsValue) -> web_sys::Animation;
+}
+
+pub(crate) fn animate(elem: &Element, keyframes: &Array, options: &JsValue) -> web_sys::Animation {
+    elem.unchecked_ref::<AnimatableElement>().animate(keyframes, options)
+}
+
 #[track_caller]
 pub(crate) fn create_stylesheet(css: Option<&str>) -> CssStyleSheet {
     DOCUMENT.with(|document| {
@@ -64,6 +219,17 @@ pub(crate) fn create_stylesheet(css: Option<&str>) -> CssStyleSheet {
     })
 }
 
+#[track_caller]
+pub(crate) fn create_constructable_stylesheet() -> CssStyleSheet {
+    CssStyleSheet::new().unwrap_js()
+}
+
+pub(crate) fn adopt_stylesheet(root: &ShadowRoot, sheet: &CssStyleSheet) {
+    // Older browsers use a FrozenArray, so we have to create a new Array instead of using push
+    let sheets = root.adopted_style_sheets().concat(&Array::of1(sheet));
+    root.set_adopted_style_sheets(&sheets);
+}
+
 #[track_caller]
 pub(crate) fn make_rule(sheet: &CssStyleSheet, rule: &str) -> Result<CssRule, JsValue> {
     let rules = sheet.css_rules().unwrap_js();
@@ -74,6 +240,64 @@ pub(crate) fn make_rule(sheet: &CssStyleSheet, rule: &str) -> Result<CssRule, Js
     Ok(rules.get(length).unwrap_throw())
 }
 
+pub(crate) fn first_nested_rule(rule: &CssRule) -> CssRule {
+    // TODO use dyn_into ?
+    let rules: CssRuleList = rule.unchecked_ref::<CssGroupingRule>().css_rules();
+    rules.get(0).unwrap_throw()
+}
+
+/// Returns the style declaration of a style rule, keyframe rule, or `@font-face` rule.
+pub(crate) fn rule_style(rule: &CssRule) -> CssStyleDeclaration {
+    if let Some(rule) = rule.dyn_ref::<CssStyleRule>() {
+        rule.style()
+
+    } else if let Some(rule) = rule.dyn_ref::<CssKeyframeRule>() {
+        rule.style()
+
+    } else if let Some(rule) = rule.dyn_ref::<CssFontFaceRule>() {
+        rule.style()
+
+    } else {
+        panic!("rule does not have styles: {}", rule.css_text());
+    }
+}
+
+pub(crate) fn selector_text(rule: &CssRule) -> Option<String> {
+    rule.dyn_ref::<CssStyleRule>().map(|rule| rule.selector_text())
+}
+
+pub(crate) fn append_keyframe(rule: &CssKeyframesRule, keyframe: &str) -> Option<CssRule> {
+    let rules = rule.css_rules();
+    let length = rules.length();
+
+    rule.append_rule(keyframe);
+
+    // appendRule doesn't throw an error if the keyframe is incorrect
+    if rules.length() > length {
+        rules.get(length)
+
+    } else {
+        None
+    }
+}
+
+#[track_caller]
+pub(crate) fn remove_rule(rule: &CssRule) {
+    if let Some(sheet) = rule.parent_style_sheet() {
+        let rules = sheet.css_rules().unwrap_js();
+
+        // The index of the rule changes when other rules are removed, so we have to search for it
+        // TODO can this be made more efficient ?
+        let index = (0..rules.length()).find(|index| {
+            rules.get(*index).as_ref() == Some(rule)
+        });
+
+        if let Some(index) = index {
+            sheet.delete_rule(index).unwrap_js();
+        }
+    }
+}
+
 
 pub(crate) fn get_element_by_id(id: &str) -> Element {
     DOCUMENT.with(|d| d.get_element_by_id(id).unwrap_throw())
@@ -161,6 +385,16 @@ pub(crate) fn append_raw(style: &CssStyleDeclaration, css: &str) {
     style.set_css_text(&(style.css_text() + css));
 }
 
+pub(crate) fn bounding_position(elem: &Element) -> (f64, f64) {
+    let rect = elem.get_bounding_client_rect();
+    (rect.left(), rect.top())
+}
+
+pub(crate) fn inline_style(elem: &Element) -> Option<CssStyleDeclaration> {
+    elem.dyn_ref::<HtmlElement>().map(|elem| elem.style())
+        .or_else(|| elem.dyn_ref::<web_sys::SvgElement>().map(|elem| elem.style()))
+}
+
 #[track_caller]
 pub(crate) fn insert_child_before(parent: &Node, child: &Node, other: &Node) {
     // TODO don't return Node ?
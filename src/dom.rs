@@ -11,15 +11,17 @@ use futures_util::FutureExt;
 use futures_channel::oneshot;
 use discard::{Discard, DiscardOnDrop};
 use wasm_bindgen::{JsValue, UnwrapThrowExt, JsCast, intern};
-use web_sys::{HtmlElement, Node, EventTarget, Element, CssRule, CssStyleRule, CssStyleSheet, CssStyleDeclaration, ShadowRoot, ShadowRootMode, ShadowRootInit, Text};
+use web_sys::{HtmlElement, Node, EventTarget, Element, CssRule, CssStyleSheet, CssStyleDeclaration, CssKeyframesRule, ShadowRoot, ShadowRootMode, ShadowRootInit, Text};
 
 use crate::bindings;
 use crate::bindings::WINDOW;
 use crate::callbacks::Callbacks;
 use crate::traits::*;
 use crate::fragment::{Fragment, FragmentBuilder};
+use crate::animation::{Keyframe, KeyframeOptions, FlipOptions, WebAnimation, animate_element};
 use crate::operations;
 use crate::operations::{for_each, spawn_future};
+use crate::static_css;
 use crate::utils::{EventListener, on, RefCounter, MutableListener, UnwrapJsExt, ValueDiscard, FnDiscard};
 
 #[cfg(doc)]
@@ -82,8 +84,11 @@ const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
 pub const HIGHEST_ZINDEX: &str = "2147483647";
 
 
-static HIDDEN_CLASS: Lazy<String> = Lazy::new(|| class! {
-    .style_important("display", "none")
+// This doesn't use `class!` because it must always be inserted at runtime, even with the `static-css` feature
+static HIDDEN_CLASS: Lazy<String> = Lazy::new(|| {
+    ClassBuilder::__internal_new(None)
+        .style_important("display", "none")
+        .__internal_done()
 });
 
 
@@ -301,7 +306,7 @@ pub fn window_size() -> impl Signal<Item = WindowSize> {
 }
 
 
-fn media_query_raw<A, F>(query: &str, mut f: F) -> (Mutable<A>, EventListener)
+pub(crate) fn media_query_raw<A, F>(query: &str, mut f: F) -> (Mutable<A>, EventListener)
     where A: PartialEq + 'static,
           F: FnMut(bool) -> A + 'static {
 
@@ -1174,6 +1179,77 @@ impl<A> DomBuilder<A> where A: AsRef<Node> {
         self
     }
 
+    /// Renders the current route.
+    ///
+    /// Whenever the route changes, the closure is called with the new route, and the [`Dom`] which it returns is rendered.
+    ///
+    /// It only re-renders when the [`matched_path`](crate::routing::Route::matched_path) changes, so if only a `#[nested]`
+    /// route changes then it won't re-render. Instead the nested route is rendered with another `route_outlet`:
+    ///
+    /// ```rust
+    /// html!("main", {
+    ///     .route_outlet(route_signal::<AppRoute>(), |route| match route {
+    ///         Some(AppRoute::User { id, .. }) => Some(user_layout(id)),
+    ///         None => Some(not_found()),
+    ///     })
+    /// })
+    ///
+    /// fn user_layout(id: u32) -> Dom {
+    ///     html!("div", {
+    ///         .route_outlet(route_signal::<AppRoute>().map(|route| match route {
+    ///             Some(AppRoute::User { child, .. }) => Some(child),
+    ///             _ => None,
+    ///         }), |route| match route {
+    ///             Some(UserRoute::Profile) => Some(profile(id)),
+    ///             Some(UserRoute::Settings) => Some(settings(id)),
+    ///             None => None,
+    ///         })
+    ///     })
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn route_outlet<R, B, F>(self, route: B, f: F) -> Self
+        where R: crate::routing::Route + 'static,
+              B: Signal<Item = Option<R>> + 'static,
+              F: FnMut(Option<R>) -> Option<Dom> + 'static {
+        self.child_signal(crate::routing::outlet_signal(route, f))
+    }
+
+    /// Same as [`route_outlet`](DomBuilder::route_outlet), except it loads some data before rendering the route.
+    ///
+    /// When the route changes, the `loader` is called with the new route, and it returns a [`Future`].
+    /// While the `Future` is running it renders [`RouteLoad::Loading`](crate::routing::RouteLoad::Loading), and when
+    /// the `Future` is finished it renders [`RouteLoad::Loaded`](crate::routing::RouteLoad::Loaded) with the data:
+    ///
+    /// ```rust
+    /// html!("main", {
+    ///     .route_outlet_async(route_signal::<AppRoute>(), |route| {
+    ///         let route = route.clone();
+    ///         async move { fetch_data(&route).await }
+    ///     }, |route| match route {
+    ///         Some(RouteLoad::Loading(_)) => Some(loading_spinner()),
+    ///         Some(RouteLoad::Loaded(route, data)) => Some(render_page(route, data)),
+    ///         None => Some(not_found()),
+    ///     })
+    /// })
+    /// ```
+    ///
+    /// If the route changes while it is loading, then the old `Future` is cancelled.
+    ///
+    /// While it is loading, [`navigation_pending_signal`](crate::routing::navigation_pending_signal) is `true`.
+    #[inline]
+    #[track_caller]
+    pub fn route_outlet_async<R, B, C, D, L, F>(self, route: B, loader: L, render: F) -> Self
+        where R: crate::routing::Route + Clone + 'static,
+              B: Signal<Item = Option<R>> + 'static,
+              C: 'static,
+              D: Future<Output = C> + 'static,
+              L: FnMut(&R) -> D + 'static,
+              F: FnMut(Option<crate::routing::RouteLoad<R, C>>) -> Option<Dom> + 'static {
+        self.child_signal(crate::routing::loader_outlet_signal(route, loader, render))
+    }
+
     // TODO figure out how to make this owned rather than &mut
     #[inline]
     #[track_caller]
@@ -1187,7 +1263,66 @@ impl<A> DomBuilder<A> where A: AsRef<Node> {
     pub fn children_signal_vec<B>(mut self, children: B) -> Self
         where B: SignalVec<Item = Dom> + 'static {
 
-        operations::insert_children_signal_vec(self.element.as_ref().clone(), &mut self.callbacks, children);
+        operations::insert_children_signal_vec(self.element.as_ref().clone(), &mut self.callbacks, children, None);
+        self
+    }
+
+    /// The same as [`children_signal_vec`](DomBuilder::children_signal_vec), except moved children
+    /// smoothly animate to their new position.
+    ///
+    /// This animates [`VecDiff::Move`](futures_signals::signal_vec::VecDiff::Move), so you should reorder the children with
+    /// [`MutableVec::move_from_to`](futures_signals::signal_vec::MutableVecLockMut::move_from_to).
+    ///
+    /// The old and new children are matched by identity, so [`VecDiff::Replace`](futures_signals::signal_vec::VecDiff::Replace)
+    /// only animates the children which are reused. Using [`MutableVec::replace`](futures_signals::signal_vec::MutableVecLockMut::replace)
+    /// with [`map`](futures_signals::signal_vec::SignalVecExt::map) creates new children, so they immediately appear at their new position.
+    ///
+    /// The animation uses the `transform` style, any existing `transform` on the children is kept.
+    #[inline]
+    #[track_caller]
+    pub fn children_signal_vec_flip<B>(mut self, options: FlipOptions, children: B) -> Self
+        where B: SignalVec<Item = Dom> + 'static {
+
+        operations::insert_children_signal_vec(self.element.as_ref().clone(), &mut self.callbacks, children, Some(options));
+        self
+    }
+}
+
+impl<A> DomBuilder<A> where A: AsRef<ShadowRoot> {
+    /// Applies a [`Stylesheet`] to the shadow root.
+    ///
+    /// Normally [`class!`] and [`stylesheet!`] are global, so they don't apply inside of a shadow root.
+    ///
+    /// But if you create them with `#![stylesheet = ...]` then they are inserted into the [`Stylesheet`]
+    /// instead, and then you can use `adopt_stylesheet` to apply them to the shadow root:
+    ///
+    /// ```rust
+    /// thread_local! {
+    ///     static STYLESHEET: Stylesheet = Stylesheet::new();
+    /// }
+    ///
+    /// static MY_CLASS: Lazy<String> = Lazy::new(|| STYLESHEET.with(|stylesheet| class! {
+    ///     #![stylesheet = stylesheet]
+    ///     .style("color", "green")
+    /// }));
+    ///
+    /// html!("div", {
+    ///     .shadow_root!(ShadowRootMode::Open => {
+    ///         .apply(|dom| STYLESHEET.with(|stylesheet| dom.adopt_stylesheet(stylesheet)))
+    ///         .child(html!("span", {
+    ///             .class(&*MY_CLASS)
+    ///         }))
+    ///     })
+    /// })
+    /// ```
+    ///
+    /// [`Stylesheet`] is not `Sync`, so it must be stored in a `thread_local!` (or created in `main`)
+    /// rather than in a `static`.
+    ///
+    /// The same [`Stylesheet`] can be adopted by any number of shadow roots.
+    #[inline]
+    pub fn adopt_stylesheet(self, stylesheet: &Stylesheet) -> Self {
+        bindings::adopt_stylesheet(self.element.as_ref(), &stylesheet.sheet);
         self
     }
 }
@@ -1489,6 +1624,91 @@ impl<A> DomBuilder<A> where A: AsRef<Element> {
         self.set_scroll_into_view_signal(signal);
         self
     }
+
+
+    /// Runs a [Web Animation](https://developer.mozilla.org/en-US/docs/Web/API/Element/animate) on the element.
+    ///
+    /// The animation starts after the element is inserted into the DOM, and it is cancelled when the element is removed.
+    ///
+    /// If you need to control the animation, use [`animate_with`](DomBuilder::animate_with) instead.
+    #[inline]
+    #[track_caller]
+    pub fn animate(self, keyframes: &[Keyframe], options: &KeyframeOptions) -> Self {
+        self.animate_with(keyframes, options, |_| {})
+    }
+
+    /// The same as [`animate`](DomBuilder::animate), except it calls the closure with the [`WebAnimation`]
+    /// when the animation starts.
+    ///
+    /// This is useful for controlling the animation, or for waiting until it is finished:
+    ///
+    /// ```rust
+    /// html!("div", {
+    ///     .animate_with(&keyframes, &KeyframeOptions::new(300.0), |animation| {
+    ///         let finished = animation.finished();
+    ///
+    ///         spawn_local(async move {
+    ///             if finished.await {
+    ///                 // The animation is finished
+    ///             }
+    ///         });
+    ///     })
+    /// })
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn animate_with<F>(mut self, keyframes: &[Keyframe], options: &KeyframeOptions, f: F) -> Self
+        where F: FnOnce(&WebAnimation) + 'static {
+
+        let element: Element = self.element.as_ref().clone();
+        let keyframes = keyframes.to_vec();
+        let options = options.clone();
+
+        // This needs to use `after_insert` so that short animations aren't finished before the element is visible
+        self.callbacks.after_insert(move |callbacks| {
+            let animation = animate_element(&element, &keyframes, &options);
+            f(&animation);
+            callbacks.after_remove(FnDiscard::new(move || animation.cancel()));
+        });
+
+        self
+    }
+
+    /// Plays the animation forwards when the Signal is `true`, and backwards when the Signal is `false`.
+    ///
+    /// If the Signal is initially `false` then the animation starts paused at the beginning. If the animation
+    /// is already at the beginning (or end) then it isn't played again.
+    ///
+    /// This is useful for toggles (e.g. opening a menu). You usually want to use [`FillMode::Both`](crate::animation::FillMode::Both),
+    /// so that the styles stay applied when the animation isn't running.
+    ///
+    /// The animation starts after the element is inserted into the DOM, and it is cancelled when the element is removed.
+    #[track_caller]
+    pub fn animate_signal<B>(mut self, keyframes: &[Keyframe], options: &KeyframeOptions, signal: B) -> Self
+        where B: Signal<Item = bool> + 'static {
+
+        let element: Element = self.element.as_ref().clone();
+        let keyframes = keyframes.to_vec();
+        let options = options.clone();
+
+        // This needs to use `after_insert` so that the animation isn't played before the element is visible
+        self.callbacks.after_insert(move |callbacks| {
+            let animation = animate_element(&element, &keyframes, &options);
+            animation.pause();
+
+            callbacks.after_remove(for_each(signal, {
+                let animation = animation.clone();
+
+                move |forwards| {
+                    animation.toggle(forwards);
+                }
+            }));
+
+            callbacks.after_remove(FnDiscard::new(move || animation.cancel()));
+        });
+
+        self
+    }
 }
 
 impl<A> DomBuilder<A> where A: AsRef<HtmlElement> {
@@ -1653,61 +1873,239 @@ pub fn stylesheet_raw<A>(css: A) where A: AsStr {
 }
 
 
+/// A [constructable stylesheet](https://developer.mozilla.org/en-US/docs/Web/API/CSSStyleSheet/CSSStyleSheet)
+/// which [`class!`] and [`stylesheet!`] rules can be inserted into.
+///
+/// Unlike the global stylesheet, it does not apply to the page, instead it must be
+/// added to a shadow root with [`DomBuilder::adopt_stylesheet`].
+///
+/// It is cheap to clone, the clones all refer to the same stylesheet.
+#[derive(Debug, Clone)]
+pub struct Stylesheet {
+    sheet: CssStyleSheet,
+}
+
+impl Stylesheet {
+    #[inline]
+    #[track_caller]
+    pub fn new() -> Self {
+        Self {
+            sheet: bindings::create_constructable_stylesheet(),
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_sheet(&self) -> &CssStyleSheet {
+        &self.sheet
+    }
+}
+
+impl Default for Stylesheet {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+thread_local! {
+    // TODO can this be made faster ?
+    // TODO somehow share this safely between threads ?
+    static STYLESHEET: CssStyleSheet = bindings::create_stylesheet(None);
+}
+
+
+/// This is returned by the [`stylesheet_handle!`] macro.
+///
+/// When the [`StylesheetHandle`] is discarded using `handle.discard()` it
+/// will remove the CSS rule from the stylesheet and it will stop all of
+/// the `style_signal` signals.
+///
+/// If you never call `handle.discard()` then the CSS rule will exist forever.
+pub struct StylesheetHandle {
+    rule: CssRule,
+    children: Vec<StylesheetHandle>,
+    callbacks: Callbacks,
+}
+
+impl StylesheetHandle {
+    #[inline]
+    fn new(rule: CssRule, children: Vec<StylesheetHandle>, mut callbacks: Callbacks) -> Self {
+        callbacks.trigger_after_insert();
+
+        // This prevents it from triggering after_remove
+        callbacks.leak();
+
+        Self { rule, children, callbacks }
+    }
+
+    // Records the CSS of the rule (and its nested rules) so that it can be extracted
+    fn push_static_css(&self) {
+        static_css::push(self.rule.css_text());
+
+        for child in self.children.iter() {
+            child.push_static_css();
+        }
+    }
+}
+
+impl Discard for StylesheetHandle {
+    #[inline]
+    #[track_caller]
+    fn discard(self) {
+        for child in self.children {
+            child.discard();
+        }
+
+        bindings::remove_rule(&self.rule);
+        self.callbacks.discard();
+    }
+}
+
+
 // TODO better warning message for must_use
 #[must_use]
 pub struct StylesheetBuilder {
+    sheet: CssStyleSheet,
+    // The at-rules (e.g. `@media`) which the rule is nested inside of
+    at_rules: Vec<String>,
+    // The top-level rule, this is what gets removed from the stylesheet
+    rule: CssRule,
+    // The innermost rule, which contains the styles
+    inner: CssRule,
     element: CssStyleDeclaration,
+    // Nested rules which are removed at the same time as this rule
+    children: Vec<StylesheetHandle>,
+    // Static rules can be extracted into a `.css` file with the `static-css` feature
+    is_static: bool,
     callbacks: Callbacks,
 }
 
-// TODO remove the CssStyleRule when this is discarded
 impl StylesheetBuilder {
-    fn __internal_rules<A>(rules: &A) -> CssRule where A: MultiStr {
-        // TODO can this be made faster ?
-        // TODO somehow share this safely between threads ?
-        thread_local! {
-            static STYLESHEET: CssStyleSheet = bindings::create_stylesheet(None);
-        }
-
-        STYLESHEET.with(move |stylesheet| {
-            let mut failed = vec![];
+    fn __internal_rules<A>(stylesheet: &CssStyleSheet, rules: &A) -> CssRule where A: MultiStr {
+        let mut failed = vec![];
 
-            let okay = rules.find_map(|rule| {
-                // TODO maybe intern the rule ?
-                if let Ok(declaration) = bindings::make_rule(stylesheet, rule) {
-                    Some(declaration)
-
-                } else {
-                    failed.push(String::from(rule));
-                    None
-                }
-            });
-
-            if let Some(okay) = okay {
-                okay
+        let okay = rules.find_map(|rule| {
+            // TODO maybe intern the rule ?
+            if let Ok(declaration) = bindings::make_rule(stylesheet, rule) {
+                Some(declaration)
 
             } else {
-                // TODO maybe make this configurable
-                panic!("selectors are incorrect:\n  {}", failed.join("\n  "));
+                failed.push(String::from(rule));
+                None
             }
-        })
+        });
+
+        if let Some(okay) = okay {
+            okay
+
+        } else {
+            // TODO maybe make this configurable
+            panic!("selectors are incorrect:\n  {}", failed.join("\n  "));
+        }
     }
 
     #[doc(hidden)]
     #[inline]
     pub fn __internal_stylesheet<A>(rules: A) -> Self where A: MultiStr {
-        let element = Self::__internal_rules(&rules).unchecked_into::<CssStyleRule>();
+        STYLESHEET.with(|stylesheet| Self::__internal_stylesheet_in(stylesheet, rules))
+    }
+
+    fn from_rule(stylesheet: &CssStyleSheet, at_rules: Vec<String>, rule: CssRule, inner: CssRule) -> Self {
+        let element = bindings::rule_style(&inner);
 
         Self {
-            element: element.style(),
+            sheet: stylesheet.clone(),
+            at_rules,
+            rule,
+            inner,
+            element,
+            children: vec![],
+            is_static: false,
             callbacks: Callbacks::new(),
         }
     }
 
+    // Creates a new style rule which is nested inside of the at-rules
+    #[track_caller]
+    fn new_nested<A>(stylesheet: &CssStyleSheet, at_rules: Vec<String>, selectors: A) -> Self where A: MultiStr {
+        let rule = Self::__internal_rules(stylesheet, &MapMultiStr::new(selectors, |selector| {
+            at_rules.iter().rev().fold(format!("{} {{}}", selector), |css, at_rule| {
+                format!("{} {{ {} }}", at_rule, css)
+            })
+        }));
+
+        let inner = at_rules.iter().fold(rule.clone(), |rule, _| bindings::first_nested_rule(&rule));
+
+        Self::from_rule(stylesheet, at_rules, rule, inner)
+    }
+
+    // Creates a new style rule which is nested inside of the same at-rules as this rule
+    #[track_caller]
+    fn nested<A>(&self, selectors: A) -> Self where A: MultiStr {
+        let mut nested = Self::new_nested(&self.sheet, self.at_rules.clone(), selectors);
+        nested.is_static = self.is_static;
+        nested
+    }
+
+    // With the `static-css` feature, static styles are loaded from the `.css` file instead
+    #[inline]
+    fn apply_static_styles(&self) -> bool {
+        !self.is_static || static_css::apply_static()
+    }
+
+    #[track_caller]
+    fn at_rule<F>(mut self, at_rule: String, f: F) -> Self where F: FnOnce(Self) -> Self {
+        let selector = match bindings::selector_text(&self.inner) {
+            Some(selector) => selector,
+            None => panic!("{} can only be used inside of a style rule", at_rule),
+        };
+
+        let mut at_rules = self.at_rules.clone();
+        at_rules.push(at_rule);
+
+        let mut child = Self::new_nested(&self.sheet, at_rules, selector);
+        child.is_static = self.is_static;
+
+        let child = f(child);
+        self.children.push(child.__internal_done_handle());
+        self
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_stylesheet_in<A>(stylesheet: &CssStyleSheet, rules: A) -> Self where A: MultiStr {
+        let rule = Self::__internal_rules(stylesheet, &rules);
+        Self::from_rule(stylesheet, vec![], rule.clone(), rule)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_stylesheet_static<A>(rules: A) -> Self where A: MultiStr {
+        let mut stylesheet = Self::__internal_stylesheet(rules);
+        stylesheet.is_static = true;
+        stylesheet
+    }
+
     #[doc(hidden)]
     #[inline]
     pub fn __internal_new<A>(rules: A) -> Self where A: MultiStr {
-        Self::__internal_stylesheet(MapMultiStr::new(rules, |rule| format!("{} {{}}", rule)))
+        STYLESHEET.with(|stylesheet| Self::__internal_new_in(stylesheet, rules))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_new_in<A>(stylesheet: &CssStyleSheet, rules: A) -> Self where A: MultiStr {
+        Self::new_nested(stylesheet, vec![], rules)
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_new_static<A>(rules: A) -> Self where A: MultiStr {
+        let mut stylesheet = Self::__internal_new(rules);
+        stylesheet.is_static = true;
+        stylesheet
     }
 
     #[inline]
@@ -1715,7 +2113,9 @@ impl StylesheetBuilder {
     pub fn style<B, C>(self, name: B, value: C) -> Self
         where B: MultiStr,
               C: MultiStr {
-        set_style(&self.element, &name, value, false);
+        if self.apply_static_styles() {
+            set_style(&self.element, &name, value, false);
+        }
         self
     }
 
@@ -1724,7 +2124,9 @@ impl StylesheetBuilder {
     pub fn style_important<B, C>(self, name: B, value: C) -> Self
         where B: MultiStr,
               C: MultiStr {
-        set_style(&self.element, &name, value, true);
+        if self.apply_static_styles() {
+            set_style(&self.element, &name, value, true);
+        }
         self
     }
 
@@ -1733,11 +2135,13 @@ impl StylesheetBuilder {
     pub fn style_unchecked<B, C>(self, name: B, value: C) -> Self
         where B: AsStr,
               C: AsStr {
-        name.with_str(|name| {
-            value.with_str(|value| {
-                bindings::set_style(&self.element, intern(name), value, false);
+        if self.apply_static_styles() {
+            name.with_str(|name| {
+                value.with_str(|value| {
+                    bindings::set_style(&self.element, intern(name), value, false);
+                });
             });
-        });
+        }
         self
     }
 
@@ -1798,56 +2202,240 @@ impl StylesheetBuilder {
     #[inline]
     #[track_caller]
     pub fn raw<B>(self, css: B) -> Self where B: AsStr {
-        css.with_str(|css| {
-            bindings::append_raw(&self.element, css);
-        });
+        if self.apply_static_styles() {
+            css.with_str(|css| {
+                bindings::append_raw(&self.element, css);
+            });
+        }
 
         self
     }
 
-    // TODO return a Handle
+    /// Creates a nested [`@media`](https://developer.mozilla.org/en-US/docs/Web/CSS/@media) rule.
+    ///
+    /// The styles inside of the closure only apply when the media query matches:
+    ///
+    /// ```rust
+    /// stylesheet!(".foo", {
+    ///     .style("width", "100px")
+    ///     .media("(max-width: 500px)", |rule| { rule
+    ///         .style("width", "50px")
+    ///     })
+    /// })
+    /// ```
+    ///
+    /// The closure can also create more nested rules, for example a `.supports` inside of a `.media`.
     #[inline]
     #[track_caller]
-    #[doc(hidden)]
-    pub fn __internal_done(mut self) {
-        self.callbacks.trigger_after_insert();
-
-        // This prevents it from triggering after_remove
-        self.callbacks.leak();
+    pub fn media<B, F>(self, query: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = query.with_str(|query| format!("@media {}", query));
+        self.at_rule(at_rule, f)
     }
-}
 
+    /// Creates a nested [`@supports`](https://developer.mozilla.org/en-US/docs/Web/CSS/@supports) rule.
+    ///
+    /// The styles inside of the closure only apply when the browser supports the condition:
+    ///
+    /// ```rust
+    /// stylesheet!(".foo", {
+    ///     .style("display", "block")
+    ///     .supports("(display: grid)", |rule| { rule
+    ///         .style("display", "grid")
+    ///     })
+    /// })
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn supports<B, F>(self, condition: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = condition.with_str(|condition| format!("@supports {}", condition));
+        self.at_rule(at_rule, f)
+    }
 
-// TODO better warning message for must_use
-#[must_use]
-pub struct ClassBuilder {
-    stylesheet: StylesheetBuilder,
-    class_name: String,
-}
+    /// Creates a nested [`@container`](https://developer.mozilla.org/en-US/docs/Web/CSS/@container) rule.
+    ///
+    /// The styles inside of the closure only apply when the container query matches:
+    ///
+    /// ```rust
+    /// stylesheet!(".foo", {
+    ///     .container("sidebar (min-width: 400px)", |rule| { rule
+    ///         .style("display", "flex")
+    ///     })
+    /// })
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn container<B, F>(self, condition: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = condition.with_str(|condition| format!("@container {}", condition));
+        self.at_rule(at_rule, f)
+    }
 
-impl ClassBuilder {
-    #[doc(hidden)]
     #[inline]
     #[track_caller]
-    pub fn __internal_new(name: Option<&str>) -> Self {
-        let class_name = __internal::make_class_id(name);
+    #[doc(hidden)]
+    pub fn __internal_done(self) {
+        let is_static = self.is_static;
 
-        Self {
-            // TODO make this more efficient ?
-            stylesheet: StylesheetBuilder::__internal_stylesheet(&format!(".{} {{}}", class_name)),
-            class_name,
+        // The handle is dropped without being discarded, so the rule exists forever
+        let handle = self.__internal_done_handle();
+
+        if is_static {
+            handle.push_static_css();
         }
     }
 
-    #[doc(hidden)]
     #[inline]
     #[track_caller]
-    pub fn __internal_class_name(&self) -> &str {
-        &self.class_name
+    #[doc(hidden)]
+    pub fn __internal_done_handle(self) -> StylesheetHandle {
+        StylesheetHandle::new(self.rule, self.children, self.callbacks)
     }
+}
 
-    #[inline]
-    #[track_caller]
+
+/// This is returned by the [`class_handle!`] macro.
+///
+/// When the [`ClassHandle`] is discarded using `handle.discard()` it
+/// will remove all of the CSS rules for the class (including [`pseudo!`] and [`nested!`] rules)
+/// and it will stop all of the `style_signal` signals.
+///
+/// If you never call `handle.discard()` then the class will exist forever.
+pub struct ClassHandle {
+    class_name: String,
+    stylesheet: StylesheetHandle,
+}
+
+impl ClassHandle {
+    /// Returns the unique class name, which can be given to [`DomBuilder::class`].
+    #[inline]
+    pub fn class_name(&self) -> &str {
+        &self.class_name
+    }
+}
+
+impl Discard for ClassHandle {
+    #[inline]
+    #[track_caller]
+    fn discard(self) {
+        self.stylesheet.discard();
+    }
+}
+
+
+// TODO better warning message for must_use
+#[must_use]
+pub struct ClassBuilder {
+    stylesheet: StylesheetBuilder,
+    class_name: String,
+    // The selector which `&` refers to, this is different from the class name for nested rules
+    selector: String,
+}
+
+impl ClassBuilder {
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new(name: Option<&str>) -> Self {
+        STYLESHEET.with(|stylesheet| Self::__internal_new_in(stylesheet, name))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_in(stylesheet: &CssStyleSheet, name: Option<&str>) -> Self {
+        Self::new_named(stylesheet, __internal::make_class_id(name))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_static(name: Option<&str>, location: &'static str, styles: &'static str, is_static: bool) -> Self {
+        // The styles might be different every time the macro runs, so each class needs its own name
+        if !is_static {
+            return Self::__internal_new(name);
+        }
+
+        // The class name must be the same in every build, so it can't use the global counter
+        let class_name = if cfg!(feature = "static-css") {
+            static_css::make_static_id(name.unwrap_or("__class_"), location, styles)
+
+        } else {
+            __internal::make_class_id(name)
+        };
+
+        let mut class = STYLESHEET.with(|stylesheet| Self::new_named(stylesheet, class_name));
+        class.stylesheet.is_static = true;
+        class
+    }
+
+    #[track_caller]
+    fn new_named(stylesheet: &CssStyleSheet, class_name: String) -> Self {
+        let selector = format!(".{}", class_name);
+
+        Self {
+            // TODO make this more efficient ?
+            stylesheet: StylesheetBuilder::__internal_new_in(stylesheet, &selector),
+            class_name,
+            selector,
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_class_name(&self) -> &str {
+        &self.class_name
+    }
+
+    #[track_caller]
+    fn new_nested<A>(&self, selectors: A) -> Self where A: MultiStr {
+        let stylesheet = self.stylesheet.nested(selectors);
+
+        // This uses the browser's selector, because it might have used a fallback selector
+        let selector = bindings::selector_text(&stylesheet.inner).unwrap_throw();
+
+        Self {
+            stylesheet,
+            class_name: self.class_name.clone(),
+            selector,
+        }
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_pseudo<A>(&self, pseudos: A) -> Self where A: MultiStr {
+        self.new_nested(__internal::Pseudo::new(&self.selector, pseudos))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_nested<A>(&self, selectors: A) -> Self where A: MultiStr {
+        self.new_nested(__internal::Nested::new(&self.selector, selectors))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_push_nested(mut self, nested: Self) -> Self {
+        self.stylesheet.children.push(nested.stylesheet.__internal_done_handle());
+        self
+    }
+
+    #[track_caller]
+    fn at_rule<F>(mut self, at_rule: String, f: F) -> Self where F: FnOnce(Self) -> Self {
+        let class_name = self.class_name.clone();
+        let selector = self.selector.clone();
+
+        self.stylesheet = self.stylesheet.at_rule(at_rule, move |stylesheet| {
+            f(Self { stylesheet, class_name, selector }).stylesheet
+        });
+
+        self
+    }
+
+    #[inline]
+    #[track_caller]
     pub fn style<B, C>(mut self, name: B, value: C) -> Self
         where B: MultiStr,
               C: MultiStr {
@@ -1934,19 +2522,241 @@ impl ClassBuilder {
         self
     }
 
-    // TODO return a Handle ?
+    /// Creates a nested [`@media`](https://developer.mozilla.org/en-US/docs/Web/CSS/@media) rule for the class.
+    ///
+    /// The styles inside of the closure only apply when the media query matches:
+    ///
+    /// ```rust
+    /// class! {
+    ///     .style("width", "100px")
+    ///     .media("(max-width: 500px)", |class| { class
+    ///         .style("width", "50px")
+    ///     })
+    /// }
+    /// ```
+    ///
+    /// If you want to use macros (like [`pseudo!`]) inside of the closure, you can use [`apply_methods!`]:
+    ///
+    /// ```rust
+    /// class! {
+    ///     .media("(max-width: 500px)", |class| apply_methods!(class, {
+    ///         .style("width", "50px")
+    ///         .pseudo!(":hover", {
+    ///             .style("width", "60px")
+    ///         })
+    ///     }))
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn media<B, F>(self, query: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = query.with_str(|query| format!("@media {}", query));
+        self.at_rule(at_rule, f)
+    }
+
+    /// Creates a nested [`@supports`](https://developer.mozilla.org/en-US/docs/Web/CSS/@supports) rule for the class.
+    ///
+    /// See [`StylesheetBuilder::supports`] for more details.
+    #[inline]
+    #[track_caller]
+    pub fn supports<B, F>(self, condition: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = condition.with_str(|condition| format!("@supports {}", condition));
+        self.at_rule(at_rule, f)
+    }
+
+    /// Creates a nested [`@container`](https://developer.mozilla.org/en-US/docs/Web/CSS/@container) rule for the class.
+    ///
+    /// See [`StylesheetBuilder::container`] for more details.
+    #[inline]
+    #[track_caller]
+    pub fn container<B, F>(self, condition: B, f: F) -> Self where B: AsStr, F: FnOnce(Self) -> Self {
+        let at_rule = condition.with_str(|condition| format!("@container {}", condition));
+        self.at_rule(at_rule, f)
+    }
+
     #[doc(hidden)]
     #[inline]
     #[track_caller]
     pub fn __internal_done(self) -> String {
-        self.stylesheet.__internal_done();
-        self.class_name
+        let is_static = self.stylesheet.is_static;
+
+        // The handle is dropped without being discarded, so the class exists forever
+        let handle = self.__internal_done_handle();
+
+        if is_static {
+            handle.stylesheet.push_static_css();
+        }
+
+        handle.class_name
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_done_handle(self) -> ClassHandle {
+        ClassHandle {
+            class_name: self.class_name,
+            stylesheet: self.stylesheet.__internal_done_handle(),
+        }
+    }
+}
+
+
+/// This is returned by the [`keyframes_handle!`] macro.
+///
+/// When the [`KeyframesHandle`] is discarded using `handle.discard()` it
+/// will remove the `@keyframes` rule and it will stop all of the `style_signal`
+/// signals inside of the keyframes.
+///
+/// If you never call `handle.discard()` then the animation will exist forever.
+pub struct KeyframesHandle {
+    name: String,
+    rule: CssKeyframesRule,
+    children: Vec<StylesheetHandle>,
+}
+
+impl KeyframesHandle {
+    /// Returns the unique animation name, which can be used with the `animation-name` style.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Discard for KeyframesHandle {
+    #[inline]
+    #[track_caller]
+    fn discard(self) {
+        for child in self.children {
+            child.discard();
+        }
+
+        bindings::remove_rule(&self.rule);
+    }
+}
+
+
+// TODO better warning message for must_use
+#[must_use]
+pub struct KeyframesBuilder {
+    sheet: CssStyleSheet,
+    rule: CssKeyframesRule,
+    name: String,
+    // The keyframes, they are stopped when the handle is discarded
+    children: Vec<StylesheetHandle>,
+    is_static: bool,
+}
+
+impl KeyframesBuilder {
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new(name: Option<&str>) -> Self {
+        STYLESHEET.with(|stylesheet| Self::__internal_new_in(stylesheet, name))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_in(stylesheet: &CssStyleSheet, name: Option<&str>) -> Self {
+        Self::new_named(stylesheet, __internal::make_class_id(Some(name.unwrap_or("__keyframes_"))))
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    #[track_caller]
+    pub fn __internal_new_static(name: Option<&str>, location: &'static str, styles: &'static str, is_static: bool) -> Self {
+        // The styles might be different every time the macro runs, so each animation needs its own name
+        if !is_static {
+            return Self::__internal_new(name);
+        }
+
+        let name = name.unwrap_or("__keyframes_");
+
+        // The animation name must be the same in every build, so it can't use the global counter
+        let name = if cfg!(feature = "static-css") {
+            static_css::make_static_id(name, location, styles)
+
+        } else {
+            __internal::make_class_id(Some(name))
+        };
+
+        let mut keyframes = STYLESHEET.with(|stylesheet| Self::new_named(stylesheet, name));
+        keyframes.is_static = true;
+        keyframes
+    }
+
+    #[track_caller]
+    fn new_named(stylesheet: &CssStyleSheet, name: String) -> Self {
+        let rule = StylesheetBuilder::__internal_rules(stylesheet, &format!("@keyframes {} {{}}", name));
+
+        Self {
+            sheet: stylesheet.clone(),
+            rule: rule.unchecked_into(),
+            name,
+            children: vec![],
+            is_static: false,
+        }
+    }
+
+    /// Adds a keyframe to the animation.
+    ///
+    /// The first argument is the [keyframe selector](https://developer.mozilla.org/en-US/docs/Web/CSS/@keyframes#keyframe_selectors)
+    /// (for example `"from"`, `"to"`, or `"50%"`).
+    ///
+    /// The second argument is a closure which sets the styles for the keyframe:
+    ///
+    /// ```rust
+    /// keyframes! {
+    ///     .keyframe("from", |frame| frame.style("opacity", "0"))
+    ///     .keyframe("50%", |frame| frame.style("opacity", "0.2"))
+    ///     .keyframe("to", |frame| frame.style("opacity", "1"))
+    /// }
+    /// ```
+    #[inline]
+    #[track_caller]
+    pub fn keyframe<B, F>(mut self, selector: B, f: F) -> Self where B: AsStr, F: FnOnce(StylesheetBuilder) -> StylesheetBuilder {
+        let frame = selector.with_str(|selector| {
+            match bindings::append_keyframe(&self.rule, &format!("{} {{}}", selector)) {
+                Some(frame) => frame,
+                None => panic!("keyframe selector is incorrect: {}", selector),
+            }
+        });
+
+        let mut builder = StylesheetBuilder::from_rule(&self.sheet, vec![], frame.clone(), frame);
+        builder.is_static = self.is_static;
+
+        let frame = f(builder);
+        self.children.push(frame.__internal_done_handle());
+        self
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_done(self) -> String {
+        if self.is_static {
+            static_css::push(self.rule.css_text());
+        }
+
+        // The handle is dropped without being discarded, so the animation exists forever
+        self.__internal_done_handle().name
+    }
+
+    #[doc(hidden)]
+    #[inline]
+    pub fn __internal_done_handle(self) -> KeyframesHandle {
+        KeyframesHandle {
+            name: self.name,
+            rule: self.rule,
+            children: self.children,
+        }
     }
 }
 
 
 #[doc(hidden)]
 pub mod __internal {
+    use std::borrow::Cow;
     use std::sync::atomic::{AtomicU32, Ordering};
     use crate::fragment::{Fragment, FragmentBuilder, BoxFragment};
     use crate::traits::MultiStr;
@@ -1971,15 +2781,40 @@ pub mod __internal {
     }
 
 
+    // If the parent is a selector list then it must be wrapped in :is(), otherwise the combinators would be wrong
+    fn parent_selector(parent: &str) -> Cow<'_, str> {
+        if parent.contains(',') {
+            Cow::Owned(format!(":is({})", parent))
+
+        } else {
+            Cow::Borrowed(parent)
+        }
+    }
+
+    /// Replaces `&` with the parent selector, the same as CSS nesting.
+    ///
+    /// If the selector doesn't contain `&` then it is treated as a descendant of the parent.
+    pub(crate) fn expand_nested(parent: &str, selector: &str) -> String {
+        let parent = parent_selector(parent);
+
+        if selector.contains('&') {
+            selector.replace('&', &parent)
+
+        } else {
+            format!("{} {}", parent, selector)
+        }
+    }
+
+
     pub struct Pseudo<'a, A> {
-        class_name: &'a str,
+        selector: &'a str,
         pseudos: A,
     }
 
     impl<'a, A> Pseudo<'a, A> where A: MultiStr {
         #[inline]
-        pub fn new(class_name: &'a str, pseudos: A) -> Self {
-            Self { class_name, pseudos }
+        pub fn new(selector: &'a str, pseudos: A) -> Self {
+            Self { selector, pseudos }
         }
     }
 
@@ -1987,7 +2822,29 @@ pub mod __internal {
         #[inline]
         fn find_map<B, F>(&self, mut f: F) -> Option<B> where F: FnMut(&str) -> Option<B> {
             self.pseudos.find_map(|x| {
-                f(&format!(".{}{}", self.class_name, x))
+                f(&format!("{}{}", parent_selector(self.selector), x))
+            })
+        }
+    }
+
+
+    pub struct Nested<'a, A> {
+        selector: &'a str,
+        selectors: A,
+    }
+
+    impl<'a, A> Nested<'a, A> where A: MultiStr {
+        #[inline]
+        pub fn new(selector: &'a str, selectors: A) -> Self {
+            Self { selector, selectors }
+        }
+    }
+
+    impl<'a, A> MultiStr for Nested<'a, A> where A: MultiStr {
+        #[inline]
+        fn find_map<B, F>(&self, mut f: F) -> Option<B> where F: FnMut(&str) -> Option<B> {
+            self.selectors.find_map(|x| {
+                f(&expand_nested(self.selector, x))
             })
         }
     }
@@ -2017,7 +2874,7 @@ pub mod __internal {
 
 #[cfg(test)]
 mod tests {
-    use super::{DomBuilder, text_signal, RefFn};
+    use super::{DomBuilder, text_signal, RefFn, Stylesheet};
     use crate::{html, shadow_root, ShadowRootMode, with_cfg};
     use futures_signals::signal::{always, SignalExt};
     use once_cell::sync::Lazy;
@@ -2163,6 +3020,315 @@ mod tests {
         });
     }
 
+    #[test]
+    fn adopt_stylesheet() {
+        let sheet = Stylesheet::new();
+
+        stylesheet!(#![stylesheet = &sheet] "span", {
+            .style("color", "green")
+        });
+
+        let class = class! {
+            #![stylesheet = &sheet]
+            #![prefix = "foo"]
+            .style("color", "green")
+            .pseudo!(":hover", {
+                .style("color", "blue")
+            })
+        };
+
+        let _a = html!("div", {
+            .shadow_root!(ShadowRootMode::Open => {
+                .adopt_stylesheet(&sheet)
+                .with_node!(root => {
+                    .apply(|dom| {
+                        let adopted = root.adopted_style_sheets();
+                        assert_eq!(adopted.length(), 1);
+                        assert!(adopted.includes(sheet.__internal_sheet(), 0));
+                        dom
+                    })
+                })
+                .children(&mut [
+                    html!("span", {
+                        .class(&class)
+                    })
+                ])
+            })
+        });
+    }
+
+    #[test]
+    fn class_instances() {
+        fn dynamic_class(width: &'static str) -> String {
+            class! {
+                .style("color", "green")
+                .style_signal("width", always(width))
+            }
+        }
+
+        fn static_class() -> String {
+            class! {
+                .style("color", "green")
+            }
+        }
+
+        // Each instance has its own signals, so they can't share the same class
+        assert_ne!(dynamic_class("10px"), dynamic_class("20px"));
+
+        #[cfg(feature = "static-css")]
+        assert_eq!(static_class(), static_class());
+
+        #[cfg(not(feature = "static-css"))]
+        assert_ne!(static_class(), static_class());
+    }
+
+    #[test]
+    fn at_rules() {
+        use discard::Discard;
+
+        let _class = class! {
+            .style("width", "100px")
+            .media("(max-width: 500px)", |class| apply_methods!(class, {
+                .style("width", "50px")
+                .supports("(display: grid)", |class| { class
+                    .style("display", "grid")
+                })
+                .pseudo!(":hover", {
+                    .style("width", "60px")
+                })
+            }))
+        };
+
+        let _name = keyframes! {
+            .keyframe("from", |frame| frame.style("opacity", "0"))
+            .keyframe("to", |frame| frame.style("opacity", "1"))
+        };
+
+        let handle = keyframes_handle! {
+            .keyframe("from", |frame| frame.style_signal("opacity", always("0")))
+            .keyframe("to", |frame| frame.style("opacity", "1"))
+        };
+
+        let _a = html!("div", {
+            .style("animation-name", handle.name())
+        });
+
+        handle.discard();
+
+        font_face! {
+            .style("font-family", "\"Foo\"")
+        };
+    }
+
+    #[test]
+    fn nested_selectors() {
+        use super::__internal::expand_nested;
+
+        assert_eq!(expand_nested(".foo", "& > li"), ".foo > li");
+        assert_eq!(expand_nested(".foo", "& + &"), ".foo + .foo");
+        assert_eq!(expand_nested(".foo", ".dark &"), ".dark .foo");
+        assert_eq!(expand_nested(".foo", "span"), ".foo span");
+        assert_eq!(expand_nested(".foo > li, .foo > p", "&:hover"), ":is(.foo > li, .foo > p):hover");
+    }
+
+    #[test]
+    fn nested() {
+        let _class = class! {
+            .nested!("& > li", {
+                .style("color", "green")
+                .pseudo!(":hover", {
+                    .style("color", "blue")
+                })
+                .nested!(["& + &", "& ~ &"], {
+                    .style("margin-top", "5px")
+                })
+            })
+        };
+    }
+
+    #[test]
+    fn active_link() {
+        use crate::{link, active_link, intercept_links};
+        use crate::routing::LinkMatch;
+
+        let _a = link!("/users", {
+            .active_link!("/users", "active")
+        });
+
+        let _a = html!("main", {
+            .intercept_links!()
+        });
+
+        let _a = link!(String::from("/users?tab=posts"), {
+            .active_link!("/users?tab=posts", ["active", "selected"], LinkMatch::prefix().with_query())
+        });
+    }
+
+    #[test]
+    fn query_param() {
+        use crate::routing::{bind_query_param, query_param_signal};
+
+        let page = futures_signals::signal::Mutable::new(1);
+
+        let _a = html!("div", {
+            .future(bind_query_param("page", page.clone(), 1))
+            .text_signal(query_param_signal::<String>("q").map(|q| q.unwrap_or_default()))
+        });
+    }
+
+    #[test]
+    fn url() {
+        let url = crate::routing::url();
+
+        let _a = html!("div", {
+            .text_signal(url.signal_cloned())
+            .text_signal(crate::routing::url().signal_ref(|url| url.len().to_string()))
+        });
+
+        let _url: String = url.get_cloned();
+
+        let handle = crate::routing::url_handle();
+
+        let _a = html!("div", {
+            .text_signal(handle.signal_cloned())
+        });
+
+        let _url: String = handle.lock_ref().clone();
+    }
+
+    #[test]
+    fn animate() {
+        use crate::animation::{Keyframe, KeyframeOptions, FillMode};
+
+        let keyframes = [
+            Keyframe::new().style("opacity", "0").style("transform", "translateY(10px)"),
+            Keyframe::new().style("opacity", "1").style("transform", "none"),
+        ];
+
+        let _a = html!("div", {
+            .animate(&keyframes, &KeyframeOptions::new(300.0))
+            .animate_with(&keyframes, &KeyframeOptions::new(300.0), |animation| {
+                let _finished = animation.finished();
+                animation.reverse();
+            })
+            .animate_signal(&keyframes, &KeyframeOptions {
+                easing: "ease-out".into(),
+                fill: FillMode::Both,
+                ..KeyframeOptions::new(200.0)
+            }, always(true))
+        });
+    }
+
+    #[test]
+    fn children_signal_vec_flip() {
+        use futures_signals::signal_vec::{MutableVec, SignalVecExt};
+        use wasm_bindgen::JsCast;
+        use crate::animation::{FlipOptions, easing, testing};
+        use crate::{append_dom, body};
+
+        testing::use_virtual_clock();
+
+        let items = MutableVec::new_with_values(vec![1, 2, 3]);
+
+        let list = html!("ul", {
+            .children_signal_vec_flip(FlipOptions {
+                duration: 500.0,
+                easing: easing::linear,
+            }, items.signal_vec().map(|item| {
+                html!("li", {
+                    .style("height", "10px")
+                    .style("transform", "scale(1)")
+                    .text(&item.to_string())
+                })
+            }))
+        });
+
+        let element: HtmlElement = list.element.clone().unchecked_into();
+        let _handle = append_dom(&body(), list);
+
+        let transform = |index: usize| {
+            let mut child = element.first_element_child().unwrap();
+
+            for _ in 0..index {
+                child = child.next_element_sibling().unwrap();
+            }
+
+            child.unchecked_into::<HtmlElement>().style().get_property_value("transform").unwrap()
+        };
+
+        testing::advance(0.0);
+        assert_eq!(transform(0), "scale(1)");
+
+        // The first child moves down, so it starts 20px above its new position
+        items.lock_mut().move_from_to(0, 2);
+        testing::advance(0.0);
+        assert_eq!(transform(0), "translate(0px, 10px) scale(1)");
+        assert_eq!(transform(2), "translate(0px, -20px) scale(1)");
+
+        testing::advance(250.0);
+        assert_eq!(transform(2), "translate(0px, -10px) scale(1)");
+
+        // The existing transform is restored when it finishes
+        testing::advance(250.0);
+        assert_eq!(transform(0), "scale(1)");
+        assert_eq!(transform(2), "scale(1)");
+
+        // `replace` creates new children, so they aren't animated
+        items.lock_mut().replace(vec![1, 2, 3]);
+        testing::advance(0.0);
+        assert_eq!(transform(2), "scale(1)");
+    }
+
+    #[test]
+    fn route_outlet() {
+        use crate::routes;
+        use crate::routing::{route_signal, RouteLoad};
+
+        routes! {
+            #[derive(Clone)]
+            enum UserRoute {
+                #[route("/")]
+                Profile,
+            }
+        }
+
+        routes! {
+            enum AppRoute {
+                #[route("/")]
+                Home,
+
+                #[route("/users/:id")]
+                User { id: u32, #[nested] child: UserRoute },
+            }
+        }
+
+        let _a = html!("div", {
+            .route_outlet(route_signal::<AppRoute>(), |route| match route {
+                Some(AppRoute::Home) => Some(html!("div", {})),
+                Some(AppRoute::User { id, .. }) => Some(html!("div", {
+                    .text(&id.to_string())
+                    .route_outlet(route_signal::<AppRoute>().map(|route| match route {
+                        Some(AppRoute::User { child, .. }) => Some(child),
+                        _ => None,
+                    }), |route| {
+                        route.map(|UserRoute::Profile| html!("span", {}))
+                    })
+                })),
+                None => None,
+            })
+        });
+
+        let _a = html!("div", {
+            .route_outlet_async(route_signal::<UserRoute>(), |_route| async { 5 }, |route| match route {
+                Some(RouteLoad::Loading(_)) => Some(html!("div", {})),
+                Some(RouteLoad::Loaded(UserRoute::Profile, data)) => Some(html!("div", {
+                    .text(&data.to_string())
+                })),
+                None => None,
+            })
+        });
+    }
+
     #[test]
     fn with_cfg() {
         let _a = html!("div", {
@@ -6,6 +6,7 @@ use futures_signals::signal_vec::SignalVec;
 use web_sys::Node;
 
 use crate::dom::{Dom, DomBuilder};
+use crate::animation::FlipOptions;
 use crate::traits::*;
 
 #[cfg(doc)]
@@ -122,6 +123,13 @@ impl<'a> FragmentBuilder<'a> {
         where B: SignalVec<Item = Dom> + 'static {
         Self(self.0.children_signal_vec(children))
     }
+
+    #[inline]
+    #[track_caller]
+    pub fn children_signal_vec_flip<B>(self, options: FlipOptions, children: B) -> Self
+        where B: SignalVec<Item = Dom> + 'static {
+        Self(self.0.children_signal_vec_flip(options, children))
+    }
 }
 
 
@@ -15,10 +15,14 @@ mod callbacks;
 mod operations;
 mod dom;
 mod fragment;
+mod static_css;
 
 pub use web_sys::ShadowRootMode;
 pub use dom::*;
 pub use fragment::*;
+
+#[cfg(feature = "static-css")]
+pub use static_css::extract_static_css;
 pub mod traits;
 pub mod animation;
 pub mod routing;
@@ -1,5 +1,5 @@
 #[cfg(doc)]
-use crate::{DomBuilder, Dom, StylesheetBuilder, ClassBuilder};
+use crate::{DomBuilder, Dom, StylesheetBuilder, ClassBuilder, KeyframesBuilder, StylesheetHandle, ClassHandle, KeyframesHandle, Stylesheet};
 
 
 #[doc(hidden)]
@@ -189,6 +189,33 @@ macro_rules! apply_methods {
 }
 
 
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __internal_location {
+    () => {
+        ::std::concat!(::std::module_path!(), ":", ::std::line!(), ":", ::std::column!())
+    };
+}
+
+// Whether all of the methods only use literals, those methods always create the same CSS, so
+// they can use a static name. Anything else might depend on runtime values, so it returns false.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __internal_is_static {
+    () => { true };
+    (.$method:ident($($arg:literal),* $(,)?) $($rest:tt)*) => {
+        $crate::__internal_is_static!($($rest)*)
+    };
+    (.$method:ident!($($arg:literal),*, { $($methods:tt)* }) $($rest:tt)*) => {
+        ($crate::__internal_is_static!($($methods)*) && $crate::__internal_is_static!($($rest)*))
+    };
+    (.keyframe($selector:literal, |$frame:ident| $builder:ident $(.$method:ident($($arg:literal),* $(,)?))*) $($rest:tt)*) => {
+        $crate::__internal_is_static!($($rest)*)
+    };
+    ($($rest:tt)*) => { false };
+}
+
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! __internal_builder {
@@ -422,13 +449,68 @@ macro_rules! dom_builder {
 /// ```
 ///
 /// The block uses the [`apply_methods!`] macro, see the docs for [`apply_methods!`] for more details.
+///
+/// By default the rule is inserted into a global stylesheet, but you can use `#![stylesheet = ...]`
+/// to insert it into a [`Stylesheet`] instead, which can then be used inside of a shadow root:
+///
+/// ```rust
+/// MY_STYLESHEET.with(|stylesheet| {
+///     stylesheet!(#![stylesheet = stylesheet] "span", {
+///         .style("color", "green")
+///     });
+/// });
+/// ```
+///
+/// See [`DomBuilder::adopt_stylesheet`] for more details.
 #[macro_export]
 macro_rules! stylesheet {
+    (#![stylesheet = $sheet:expr] $rule:expr) => {
+        $crate::stylesheet!(#![stylesheet = $sheet] $rule, {})
+    };
+    (#![stylesheet = $sheet:expr] $rule:expr, { $($methods:tt)* }) => {
+        $crate::StylesheetBuilder::__internal_done($crate::apply_methods!($crate::StylesheetBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), $rule), { $($methods)* }))
+    };
     ($rule:expr) => {
         $crate::stylesheet!($rule, {})
     };
     ($rule:expr, { $($methods:tt)* }) => {
-        $crate::StylesheetBuilder::__internal_done($crate::apply_methods!($crate::StylesheetBuilder::__internal_new($rule), { $($methods)* }))
+        $crate::StylesheetBuilder::__internal_done($crate::apply_methods!($crate::StylesheetBuilder::__internal_new_static($rule), { $($methods)* }))
+    };
+}
+
+
+/// The same as [`stylesheet!`] except it returns a [`StylesheetHandle`].
+///
+/// When the [`StylesheetHandle`] is discarded, the CSS rule is removed from the page:
+///
+/// ```rust
+/// use discard::Discard;
+///
+/// let handle = stylesheet_handle!("div.foo", {
+///     .style("color", "green")
+///     .style_signal("width", ...)
+/// });
+///
+/// // This removes the CSS rule and stops the `style_signal`
+/// handle.discard();
+/// ```
+///
+/// This is useful for styles which only exist temporarily (for example per-route styles).
+///
+/// It also supports `#![stylesheet = ...]`, the same as [`stylesheet!`].
+#[macro_export]
+macro_rules! stylesheet_handle {
+    (#![stylesheet = $sheet:expr] $rule:expr) => {
+        $crate::stylesheet_handle!(#![stylesheet = $sheet] $rule, {})
+    };
+    (#![stylesheet = $sheet:expr] $rule:expr, { $($methods:tt)* }) => {
+        $crate::StylesheetBuilder::__internal_done_handle($crate::apply_methods!($crate::StylesheetBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), $rule), { $($methods)* }))
+    };
+    ($rule:expr) => {
+        $crate::stylesheet_handle!($rule, {})
+    };
+    ($rule:expr, { $($methods:tt)* }) => {
+        $crate::StylesheetBuilder::__internal_done_handle($crate::apply_methods!($crate::StylesheetBuilder::__internal_new($rule), { $($methods)* }))
     };
 }
 
@@ -491,13 +573,202 @@ macro_rules! stylesheet {
 /// ```
 ///
 /// Or you can use `pub(crate)` so that it can only be accessed within your crate.
+///
+/// # Shadow roots
+///
+/// By default the class is inserted into a global stylesheet, which does not apply inside of a shadow root.
+///
+/// You can use `#![stylesheet = ...]` to insert the class into a [`Stylesheet`] instead:
+///
+/// ```rust
+/// thread_local! {
+///     static MY_STYLESHEET: Stylesheet = Stylesheet::new();
+/// }
+///
+/// static MY_CLASS: Lazy<String> = Lazy::new(|| MY_STYLESHEET.with(|stylesheet| class! {
+///     #![stylesheet = stylesheet]
+///     .style("color", "green")
+/// }));
+/// ```
+///
+/// See [`DomBuilder::adopt_stylesheet`] for more details.
 #[macro_export]
 macro_rules! class {
+    (#![stylesheet = $sheet:expr] #![prefix = $name:literal] $($methods:tt)*) => {
+        $crate::class!(#![prefix = $name] #![stylesheet = $sheet] $($methods)*)
+    };
+    (#![prefix = $name:literal] #![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), Some($name)), { $($methods)* }))
+    }};
+    (#![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), None), { $($methods)* }))
+    }};
+    (#![prefix = $name:literal] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new_static(Some($name), $crate::__internal_location!(), ::std::stringify!($($methods)*), $crate::__internal_is_static!($($methods)*)), { $($methods)* }))
+    }};
+    ($($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new_static(None, $crate::__internal_location!(), ::std::stringify!($($methods)*), $crate::__internal_is_static!($($methods)*)), { $($methods)* }))
+    }};
+}
+
+
+/// The same as [`class!`] except it returns a [`ClassHandle`].
+///
+/// You can use [`ClassHandle::class_name`] to get the class name, and when the [`ClassHandle`]
+/// is discarded the CSS rules for the class are removed from the page:
+///
+/// ```rust
+/// use discard::Discard;
+///
+/// let handle = class_handle! {
+///     .style("color", "green")
+///     .pseudo!(":hover", {
+///         .style("color", "blue")
+///     })
+/// };
+///
+/// html!("div", {
+///     .class(handle.class_name())
+/// })
+///
+/// // This removes the class (including the `:hover` rule)
+/// handle.discard();
+/// ```
+///
+/// This is useful for classes which only exist temporarily (for example per-user styles).
+///
+/// It also supports `#![prefix = ...]` and `#![stylesheet = ...]`, the same as [`class!`].
+#[macro_export]
+macro_rules! class_handle {
+    (#![stylesheet = $sheet:expr] #![prefix = $name:literal] $($methods:tt)*) => {
+        $crate::class_handle!(#![prefix = $name] #![stylesheet = $sheet] $($methods)*)
+    };
+    (#![prefix = $name:literal] #![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done_handle($crate::apply_methods!($crate::ClassBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), Some($name)), { $($methods)* }))
+    }};
+    (#![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done_handle($crate::apply_methods!($crate::ClassBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), None), { $($methods)* }))
+    }};
+    (#![prefix = $name:literal] $($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done_handle($crate::apply_methods!($crate::ClassBuilder::__internal_new(Some($name)), { $($methods)* }))
+    }};
+    ($($methods:tt)*) => {{
+        $crate::ClassBuilder::__internal_done_handle($crate::apply_methods!($crate::ClassBuilder::__internal_new(None), { $($methods)* }))
+    }};
+}
+
+
+/// Creates a CSS [`@keyframes`](https://developer.mozilla.org/en-US/docs/Web/CSS/@keyframes) animation.
+///
+/// The `keyframes!` macro accepts a block of method calls. Inside of the block you can use [`KeyframesBuilder`] methods:
+///
+/// ```rust
+/// keyframes! {
+///     .keyframe("from", |frame| frame.style("opacity", "0"))
+///     .keyframe("to", |frame| frame.style("opacity", "1"))
+/// }
+/// ```
+///
+/// The block uses the [`apply_methods!`] macro, see the docs for [`apply_methods!`] for more details.
+///
+/// The `keyframes!` macro returns a `String`, which is a unique animation name. You can then use
+/// that name with the `animation-name` style:
+///
+/// ```rust
+/// static FADE_IN: Lazy<String> = Lazy::new(|| keyframes! {
+///     .keyframe("from", |frame| frame.style("opacity", "0"))
+///     .keyframe("to", |frame| frame.style("opacity", "1"))
+/// });
+///
+/// html!("div", {
+///     .style("animation-name", &*FADE_IN)
+///     .style("animation-duration", "1s")
+/// })
+/// ```
+///
+/// Just like [`class!`], it supports `#![prefix = ...]` and `#![stylesheet = ...]`.
+///
+/// The animation exists forever, use [`keyframes_handle!`](crate::keyframes_handle) if you need to remove it.
+#[macro_export]
+macro_rules! keyframes {
+    (#![stylesheet = $sheet:expr] #![prefix = $name:literal] $($methods:tt)*) => {
+        $crate::keyframes!(#![prefix = $name] #![stylesheet = $sheet] $($methods)*)
+    };
+    (#![prefix = $name:literal] #![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), Some($name)), { $($methods)* }))
+    }};
+    (#![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), None), { $($methods)* }))
+    }};
+    (#![prefix = $name:literal] $($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_static(Some($name), $crate::__internal_location!(), ::std::stringify!($($methods)*), $crate::__internal_is_static!($($methods)*)), { $($methods)* }))
+    }};
+    ($($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_static(None, $crate::__internal_location!(), ::std::stringify!($($methods)*), $crate::__internal_is_static!($($methods)*)), { $($methods)* }))
+    }};
+}
+
+
+/// The same as [`keyframes!`](crate::keyframes) except it returns a [`KeyframesHandle`].
+///
+/// You can use [`KeyframesHandle::name`] to get the animation name, and when the [`KeyframesHandle`]
+/// is discarded the `@keyframes` rule is removed from the page:
+///
+/// ```rust
+/// use discard::Discard;
+///
+/// let handle = keyframes_handle! {
+///     .keyframe("from", |frame| frame.style_signal("opacity", opacity.signal()))
+///     .keyframe("to", |frame| frame.style("opacity", "1"))
+/// };
+///
+/// html!("div", {
+///     .style("animation-name", handle.name())
+///     .style("animation-duration", "1s")
+/// })
+///
+/// // This removes the animation and stops the `style_signal`
+/// handle.discard();
+/// ```
+///
+/// It also supports `#![prefix = ...]` and `#![stylesheet = ...]`, the same as [`keyframes!`](crate::keyframes).
+#[macro_export]
+macro_rules! keyframes_handle {
+    (#![stylesheet = $sheet:expr] #![prefix = $name:literal] $($methods:tt)*) => {
+        $crate::keyframes_handle!(#![prefix = $name] #![stylesheet = $sheet] $($methods)*)
+    };
+    (#![prefix = $name:literal] #![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done_handle($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), Some($name)), { $($methods)* }))
+    }};
+    (#![stylesheet = $sheet:expr] $($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done_handle($crate::apply_methods!($crate::KeyframesBuilder::__internal_new_in($crate::Stylesheet::__internal_sheet($sheet), None), { $($methods)* }))
+    }};
     (#![prefix = $name:literal] $($methods:tt)*) => {{
-        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new(Some($name)), { $($methods)* }))
+        $crate::KeyframesBuilder::__internal_done_handle($crate::apply_methods!($crate::KeyframesBuilder::__internal_new(Some($name)), { $($methods)* }))
+    }};
+    ($($methods:tt)*) => {{
+        $crate::KeyframesBuilder::__internal_done_handle($crate::apply_methods!($crate::KeyframesBuilder::__internal_new(None), { $($methods)* }))
     }};
+}
+
+
+/// Registers a global CSS [`@font-face`](https://developer.mozilla.org/en-US/docs/Web/CSS/@font-face) rule.
+///
+/// The `font_face!` macro accepts a block of method calls. Inside of the block you can use [`StylesheetBuilder`] methods:
+///
+/// ```rust
+/// font_face! {
+///     .style("font-family", "\"My Font\"")
+///     .style("src", "url(\"/fonts/my-font.woff2\") format(\"woff2\")")
+///     .style("font-display", "swap")
+/// }
+/// ```
+///
+/// The block uses the [`apply_methods!`] macro, see the docs for [`apply_methods!`] for more details.
+#[macro_export]
+macro_rules! font_face {
     ($($methods:tt)*) => {{
-        $crate::ClassBuilder::__internal_done($crate::apply_methods!($crate::ClassBuilder::__internal_new(None), { $($methods)* }))
+        $crate::StylesheetBuilder::__internal_done($crate::apply_methods!($crate::StylesheetBuilder::__internal_stylesheet_static("@font-face {}"), { $($methods)* }))
     }};
 }
 
@@ -539,8 +810,64 @@ macro_rules! pseudo {
         $crate::pseudo!($this, $rules, {})
     };
     ($this:ident, $rules:expr, { $($methods:tt)* }) => {{
-        $crate::stylesheet!($crate::__internal::Pseudo::new($crate::ClassBuilder::__internal_class_name(&$this), $rules), { $($methods)* });
-        $this
+        let pseudo = $crate::ClassBuilder::__internal_new_pseudo(&$this, $rules);
+        let pseudo = $crate::apply_methods!(pseudo, { $($methods)* });
+        $crate::ClassBuilder::__internal_push_nested($this, pseudo)
+    }};
+}
+
+
+/// Adds a nested rule to a [`class!`] stylesheet.
+///
+/// The first argument is a CSS selector, where `&` is replaced with the class (the same as
+/// [CSS nesting](https://developer.mozilla.org/en-US/docs/Web/CSS/Nesting_selector)).
+///
+/// The second argument is a block of method calls. Inside of the block you can use [`ClassBuilder`] methods:
+///
+/// ```rust
+/// class! {
+///     .nested!("& > li", {
+///         .style("color", "green")
+///     })
+///
+///     .nested!(".dark &", {
+///         .style("color", "white")
+///     })
+/// }
+/// ```
+///
+/// The block uses the [`apply_methods!`] macro, see the docs for [`apply_methods!`] for more details.
+///
+/// If the selector doesn't contain `&` then it applies to the descendants of the class,
+/// so `"span"` is the same as `"& span"`.
+///
+/// Nested rules can also contain [`pseudo!`] and `nested!` rules, in that case `&` is the selector of the parent rule:
+///
+/// ```rust
+/// class! {
+///     .nested!("& > li", {
+///         .pseudo!(":hover", {
+///             .style("color", "blue")
+///         })
+///
+///         // This is the same as `.my-class > li + .my-class > li`
+///         .nested!("& + &", {
+///             .style("margin-top", "5px")
+///         })
+///     })
+/// }
+/// ```
+///
+/// If the first argument is an array of strings, it will try each selector in order until it finds one that works.
+#[macro_export]
+macro_rules! nested {
+    ($this:ident, $selectors:expr) => {
+        $crate::nested!($this, $selectors, {})
+    };
+    ($this:ident, $selectors:expr, { $($methods:tt)* }) => {{
+        let nested = $crate::ClassBuilder::__internal_new_nested(&$this, $selectors);
+        let nested = $crate::apply_methods!(nested, { $($methods)* });
+        $crate::ClassBuilder::__internal_push_nested($this, nested)
     }};
 }
 
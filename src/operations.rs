@@ -8,11 +8,12 @@ use futures_util::future::ready;
 use futures_signals::{cancelable_future, CancelableFutureHandle};
 use futures_signals::signal::{Signal, SignalExt};
 use futures_signals::signal_vec::{VecDiff, SignalVec, SignalVecExt};
-use web_sys::Node;
-use wasm_bindgen::UnwrapThrowExt;
+use web_sys::{Node, Element, CssStyleDeclaration};
+use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
 use wasm_bindgen_futures::spawn_local;
 
 use crate::bindings;
+use crate::animation::{FlipOptions, OnTimestampDiff, Percentage, ReducedMotion};
 use crate::dom::Dom;
 use crate::callbacks::Callbacks;
 
@@ -23,6 +24,14 @@ pub(crate) fn spawn_future<F>(future: F) -> DiscardOnDrop<CancelableFutureHandle
     // TODO make this more efficient ?
     let (handle, future) = cancelable_future(future, || ());
 
+    #[cfg(any(test, feature = "testing"))]
+    {
+        if crate::animation::testing::is_enabled() {
+            crate::animation::testing::spawn(future);
+            return handle;
+        }
+    }
+
     spawn_local(future);
 
     handle
@@ -166,8 +175,164 @@ pub(crate) fn insert_child_signal<A>(element: Node, callbacks: &mut Callbacks, s
 }
 
 
+// An element which is being animated by `Flip`
+struct FlipElement {
+    style: CssStyleDeclaration,
+    // The inline transform of the element before it was animated
+    transform: String,
+    important: bool,
+    x: f64,
+    y: f64,
+}
+
+impl FlipElement {
+    fn translate(&self, x: f64, y: f64) {
+        // The existing transform is kept, so the element is moved without changing its own transform
+        let transform = if self.transform.is_empty() {
+            format!("translate({}px, {}px)", x, y)
+
+        } else {
+            format!("translate({}px, {}px) {}", x, y, self.transform)
+        };
+
+        bindings::set_style(&self.style, "transform", &transform, self.important);
+    }
+
+    fn restore(&self) {
+        if self.transform.is_empty() {
+            bindings::remove_style(&self.style, "transform");
+
+        } else {
+            bindings::set_style(&self.style, "transform", &self.transform, self.important);
+        }
+    }
+}
+
+struct FlipState {
+    elements: Vec<FlipElement>,
+    animation: Option<OnTimestampDiff>,
+}
+
+impl FlipState {
+    fn stop(&mut self) {
+        self.animation = None;
+
+        for element in self.elements.drain(..) {
+            element.restore();
+        }
+    }
+}
+
+// The positions of the children before they were changed
+struct FlipPositions {
+    // Maps each node to its index in `positions`, a JS Map is used because the nodes are compared by identity
+    indexes: js_sys::Map,
+    positions: Vec<(f64, f64)>,
+}
+
+/// Animates children from their old position to their new position,
+/// see https://aerotwist.com/blog/flip-your-animations/
+struct Flip {
+    options: FlipOptions,
+    state: Rc<RefCell<FlipState>>,
+}
+
+impl Flip {
+    fn new(options: FlipOptions) -> Self {
+        Self {
+            options,
+            state: Rc::new(RefCell::new(FlipState {
+                elements: vec![],
+                animation: None,
+            })),
+        }
+    }
+
+    /// Measures the current position of the children, this must be called before the children are changed.
+    fn first(&self, children: &[Dom]) -> FlipPositions {
+        let indexes = js_sys::Map::new();
+        let mut positions = vec![];
+
+        // This measures the visual position, so it works correctly even if the children are already animating
+        for dom in children {
+            if let Some(element) = dom.element.dyn_ref::<Element>() {
+                indexes.set(&dom.element, &JsValue::from(positions.len() as f64));
+                positions.push(bindings::bounding_position(element));
+            }
+        }
+
+        self.state.borrow_mut().stop();
+
+        FlipPositions { indexes, positions }
+    }
+
+    fn play(&self, first: FlipPositions, children: &[Dom]) {
+        let duration = ReducedMotion::duration(None, self.options.duration);
+
+        if duration <= 0.0 {
+            return;
+        }
+
+        let mut state = self.state.borrow_mut();
+
+        for dom in children {
+            // New children don't have an old position, so they aren't animated
+            let index = match first.indexes.get(&dom.element).as_f64() {
+                Some(index) => index as usize,
+                None => continue,
+            };
+
+            let (old_x, old_y) = first.positions[index];
+
+            if let Some(element) = dom.element.dyn_ref::<Element>() {
+                if let Some(style) = bindings::inline_style(element) {
+                    let (new_x, new_y) = bindings::bounding_position(element);
+                    let x = old_x - new_x;
+                    let y = old_y - new_y;
+
+                    if x != 0.0 || y != 0.0 {
+                        let element = FlipElement {
+                            transform: bindings::get_style(&style, "transform"),
+                            important: style.get_property_priority("transform") == "important",
+                            style,
+                            x,
+                            y,
+                        };
+
+                        element.translate(x, y);
+                        state.elements.push(element);
+                    }
+                }
+            }
+        }
+
+        if !state.elements.is_empty() {
+            let easing = self.options.easing;
+            let weak = Rc::downgrade(&self.state);
+
+            state.animation = Some(OnTimestampDiff::new(move |diff| {
+                if let Some(state) = weak.upgrade() {
+                    let mut state = state.borrow_mut();
+
+                    if diff >= duration {
+                        state.stop();
+
+                    } else {
+                        let percentage = easing(Percentage::new_unchecked(diff / duration)).invert();
+
+                        for element in state.elements.iter() {
+                            element.translate(percentage.range_inclusive(0.0, element.x), percentage.range_inclusive(0.0, element.y));
+                        }
+                    }
+                }
+            }));
+        }
+    }
+}
+
+
 #[inline]
-pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callbacks, signal: A)
+pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callbacks, signal: A, flip: Option<FlipOptions>)
     where A: SignalVec<Item = Dom> + 'static {
 
     struct State {
@@ -175,15 +340,17 @@ pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callb
         marker: Node,
         is_inserted: bool,
         children: Vec<Dom>,
+        flip: Option<Flip>,
     }
 
     impl State {
-        fn new(element: Node, marker: Node) -> Rc<RefCell<Self>> {
+        fn new(element: Node, marker: Node, flip: Option<FlipOptions>) -> Rc<RefCell<Self>> {
             Rc::new(RefCell::new(State {
                 element,
                 marker,
                 is_inserted: false,
                 children: vec![],
+                flip: flip.map(Flip::new),
             }))
         }
 
@@ -209,6 +376,10 @@ pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callb
         }
 
         fn on_remove(&mut self) {
+            if let Some(flip) = &self.flip {
+                flip.state.borrow_mut().stop();
+            }
+
             for dom in self.children.drain(..) {
                 dom.callbacks.discard();
             }
@@ -223,8 +394,23 @@ pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callb
             }
         }
 
-        // TODO verify that this will drop `children`
         fn process_change(&mut self, change: VecDiff<Dom>) {
+            let flip = match change {
+                VecDiff::Move { .. } | VecDiff::Replace { .. } if self.is_inserted => {
+                    self.flip.as_ref().map(|flip| flip.first(&self.children))
+                },
+                _ => None,
+            };
+
+            self.apply_change(change);
+
+            if let Some(first) = flip {
+                self.flip.as_ref().unwrap_throw().play(first, &self.children);
+            }
+        }
+
+        // TODO verify that this will drop `children`
+        fn apply_change(&mut self, change: VecDiff<Dom>) {
             match change {
                 VecDiff::Replace { values } => {
                     self.clear();
@@ -322,7 +508,7 @@ pub(crate) fn insert_children_signal_vec<A>(element: Node, callbacks: &mut Callb
 
     bindings::append_child(&element, &marker);
 
-    let state = State::new(element, marker);
+    let state = State::new(element, marker, flip);
 
     State::after_insert(state.clone(), callbacks);
 
@@ -1,92 +1,1871 @@
 use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::task::{Context, Poll};
 
+use discard::{Discard, DiscardOnDrop};
+use pin_project::pin_project;
+use futures_channel::oneshot;
+use futures_util::future::{ready, join, FutureExt};
+use futures_signals::CancelableFutureHandle;
+use wasm_bindgen::JsValue;
 use web_sys::{EventTarget, HtmlElement};
-use futures_signals::signal::{Mutable, ReadOnlyMutable};
+use futures_signals::signal::{Mutable, MutableLockRef, ReadOnlyMutable, Signal, SignalExt};
+use wasm_bindgen_futures::spawn_local;
 
 use crate::bindings;
 use crate::bindings::WINDOW;
 use crate::dom::{Dom, DomBuilder, EventOptions};
+use crate::operations::spawn_future;
 use crate::utils::{EventListener, RefCounter, MutableListener};
-use crate::events;
+
+
+/// How the URL is stored in the browser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoutingMode {
+    /// Uses the path of the URL, such as `/users/5`.
+    ///
+    /// This requires the server to serve the app for every path.
+    #[default]
+    History,
+
+    /// Uses the fragment of the URL, such as `/#/users/5`.
+    ///
+    /// This works with static hosting, because the server always sees the same path.
+    ///
+    /// Only fragments which start with `#/` are routes, ordinary anchors (like `#section`)
+    /// are left to the browser, and the app stays on the same route.
+    Hash,
+}
+
+/// Configures how routing works, see [`configure`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RouterConfig {
+    pub mode: RoutingMode,
+
+    /// The path which the app is served from, such as `/my-app`.
+    ///
+    /// It is removed from the URLs returned by [`url`], and it is added to the URLs
+    /// passed to [`go_to_url`], [`replace_url`], and [`link!`](crate::link).
+    ///
+    /// It is ignored in [`RoutingMode::Hash`], because the path is in the fragment.
+    pub base_path: Cow<'static, str>,
+
+    /// Whether routing manages the scroll position.
+    ///
+    /// When this is enabled, [`go_to_url`] scrolls to the top of the page, or to the element which
+    /// matches the URL fragment (such as `#details`). If the element hasn't been rendered yet, then
+    /// it waits for the element to be rendered.
+    ///
+    /// The scroll position is saved for each history entry, and the back and forward buttons restore it.
+    /// The positions are saved in `sessionStorage`, so they are also restored after reloading the page.
+    pub scroll_restoration: bool,
+}
+
+thread_local! {
+    static ROUTER_CONFIG: RefCell<RouterConfig> = RefCell::new(RouterConfig::default());
+}
+
+/// Changes the [`RouterConfig`].
+///
+/// This should be called once, when your app starts:
+///
+/// ```rust
+/// routing::configure(RouterConfig {
+///     mode: RoutingMode::Hash,
+///     ..RouterConfig::default()
+/// });
+/// ```
+pub fn configure(mut config: RouterConfig) {
+    config.base_path = normalize_base(&config.base_path).into();
+
+    let old_mode = ROUTER_CONFIG.with(|old| old.replace(config).mode);
+
+    CURRENT_URL.with(|url| {
+        if let Some(url) = &mut *url.borrow_mut() {
+            if old_mode != current_mode() {
+                url.set_listener(url_listener(url.as_mutable().clone()));
+            }
+
+            change_url(url.as_mutable());
+        }
+    });
+
+    if scroll_restoration() {
+        bindings::set_manual_scroll_restoration();
+
+        // Gives a key to the first history entry
+        let state = bindings::history_state();
+
+        if bindings::history_state_key(&state).is_none() {
+            let index = bindings::history_state_index(&state).map(|index| index as u32).unwrap_or(0);
+            bindings::replace_url(&bindings::current_url(), &new_history_state(index, &bindings::history_state_payload(&state)));
+        }
+
+        SCROLL_LISTENER.with(|listener| {
+            let mut listener = listener.borrow_mut();
+
+            if listener.is_none() {
+                *listener = Some(ScrollListener::new());
+            }
+        });
+
+        // After reloading the page, the saved scroll position is restored
+        restore_scroll();
+
+    } else {
+        SCROLL_LISTENER.with(|listener| {
+            listener.borrow_mut().take();
+        });
+    }
+}
+
+#[inline]
+fn current_mode() -> RoutingMode {
+    ROUTER_CONFIG.with(|config| config.borrow().mode)
+}
+
+fn normalize_base(base: &str) -> String {
+    let base = base.trim_end_matches('/');
+
+    if base.is_empty() || base.starts_with('/') {
+        base.to_string()
+
+    } else {
+        format!("/{}", base)
+    }
+}
+
+fn add_base(base: &str, url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        format!("{}{}", base, url)
+
+    } else {
+        url.to_string()
+    }
+}
+
+fn strip_base<'a>(base: &str, path: &'a str) -> Option<&'a str> {
+    let rest = path.strip_prefix(base)?;
+
+    if rest.is_empty() {
+        Some("/")
+
+    } else if rest.starts_with('/') {
+        Some(rest)
+
+    } else {
+        None
+    }
+}
+
+// Whether the URLs are the same, except for the fragment
+fn is_same_document(a: &str, b: &str) -> bool {
+    a.split('#').next() == b.split('#').next()
+}
+
+// In hash mode, fragments which don't start with `/` are ordinary in-page anchors, not routes
+fn is_route_fragment(hash: &str) -> bool {
+    hash_path(hash).is_some()
+}
+
+// Returns the route for the fragment, or `None` if it's an ordinary in-page anchor
+fn hash_path(hash: &str) -> Option<String> {
+    let path = hash.strip_prefix('#').unwrap_or(hash);
+
+    if path.is_empty() {
+        Some("/".to_string())
+
+    } else if path.starts_with('/') {
+        Some(path.to_string())
+
+    } else {
+        None
+    }
+}
+
+#[inline]
+fn scroll_restoration() -> bool {
+    ROUTER_CONFIG.with(|config| config.borrow().scroll_restoration)
+}
+
+// Keeps the URL listener alive while scroll restoration is enabled, so that the back and forward buttons restore the scroll position
+struct ScrollListener {
+    _url: CurrentUrl,
+    _page_hide: DiscardOnDrop<EventListener>,
+}
+
+impl ScrollListener {
+    fn new() -> Self {
+        Self {
+            _url: url_handle(),
+            // Saves the scroll position before the page is reloaded
+            _page_hide: DiscardOnDrop::new(WINDOW.with(|window| {
+                EventListener::new(window, "pagehide", &EventOptions::default(), |_| {
+                    save_scroll();
+                })
+            })),
+        }
+    }
+}
+
+thread_local! {
+    static SCROLL_LISTENER: RefCell<Option<ScrollListener>> = const { RefCell::new(None) };
+
+    // This is used if sessionStorage isn't available
+    static SCROLL_POSITIONS: RefCell<HashMap<u64, (f64, f64)>> = RefCell::new(HashMap::new());
+
+    // The key of the current history entry
+    static HISTORY_KEY: Cell<Option<u64>> = const { Cell::new(None) };
+    static LAST_HISTORY_KEY: Cell<u64> = const { Cell::new(0) };
+}
+
+fn new_history_state(index: u32, payload: &JsValue) -> JsValue {
+    let key = if scroll_restoration() {
+        // It uses the current time so that the keys are unique even after reloading the page
+        Some(LAST_HISTORY_KEY.with(|last| {
+            let key = (bindings::now() as u64).max(last.get() + 1);
+            last.set(key);
+            key as f64
+        }))
+
+    } else {
+        None
+    };
+
+    bindings::create_history_state(key, Some(index as f64), payload)
+}
+
+// The index of the current history entry, this is used to undo the back and forward buttons when navigation is blocked
+fn current_history_index() -> Option<u32> {
+    bindings::history_state_index(&bindings::history_state()).map(|index| index as u32)
+}
+
+// Gives an index to the current history entry, if it doesn't already have one
+fn init_history_index() -> u32 {
+    if let Some(index) = current_history_index() {
+        index
+
+    } else {
+        let state = bindings::history_state();
+        let key = bindings::history_state_key(&state);
+
+        bindings::replace_url(&bindings::current_url(), &bindings::create_history_state(key, Some(0.0), &bindings::history_state_payload(&state)));
+
+        0
+    }
+}
+
+fn scroll_storage_key(key: u64) -> String {
+    format!("dominator-scroll:{}", key)
+}
+
+fn encode_scroll((x, y): (f64, f64)) -> String {
+    format!("{},{}", x, y)
+}
+
+fn decode_scroll(value: &str) -> Option<(f64, f64)> {
+    let (x, y) = value.split_once(',')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+fn save_scroll() {
+    if let Some(key) = HISTORY_KEY.with(|key| key.get()) {
+        let position = bindings::scroll_position();
+
+        SCROLL_POSITIONS.with(|positions| {
+            positions.borrow_mut().insert(key, position);
+        });
+
+        bindings::session_storage_set(&scroll_storage_key(key), &encode_scroll(position));
+    }
+}
+
+fn saved_scroll(key: u64) -> Option<(f64, f64)> {
+    SCROLL_POSITIONS.with(|positions| positions.borrow().get(&key).copied())
+        .or_else(|| decode_scroll(&bindings::session_storage_get(&scroll_storage_key(key))?))
+}
+
+fn current_history_key() -> Option<u64> {
+    bindings::history_state_key(&bindings::history_state()).map(|key| key as u64)
+}
+
+// Restores the scroll position of the current history entry, but only if it was saved
+fn restore_scroll() {
+    let key = current_history_key();
+
+    HISTORY_KEY.with(|old| old.set(key));
+
+    if let Some((x, y)) = key.and_then(saved_scroll) {
+        // Waits for the route to be rendered
+        bindings::next_frame(move || {
+            bindings::scroll_to(x, y);
+        });
+    }
+}
+
+fn scroll_to_fragment(id: String, frames: u32) {
+    if !bindings::scroll_to_id(&id) && frames > 0 {
+        bindings::next_frame(move || scroll_to_fragment(id, frames - 1));
+    }
+}
+
+// This must be called after the browser's URL is changed
+fn after_navigation(restore: bool) {
+    if !scroll_restoration() {
+        return;
+    }
+
+    let key = current_history_key();
+
+    HISTORY_KEY.with(|old| old.set(key));
+
+    let position = if restore {
+        key.and_then(saved_scroll)
+
+    } else {
+        None
+    };
+
+    let fragment = __internal::decode(bindings::parse_url(&current_url()).hash().trim_start_matches('#'))
+        .filter(|fragment| !fragment.is_empty());
+
+    // Waits for the new route to be rendered
+    bindings::next_frame(move || {
+        if let Some((x, y)) = position {
+            bindings::scroll_to(x, y);
+
+        } else {
+            bindings::scroll_to(0.0, 0.0);
+
+            if let Some(fragment) = fragment {
+                // Waits up to 60 frames for the element to be rendered
+                scroll_to_fragment(fragment, 60);
+            }
+        }
+    });
+}
+
+
+/// Converts a URL which is used by your app into a URL which is used by the browser.
+///
+/// This adds the [`base_path`](RouterConfig::base_path), or in [`RoutingMode::Hash`] it adds the `#`.
+/// In [`RoutingMode::Hash`] relative URLs (such as `?page=2`) are resolved against the current route.
+///
+/// This is done automatically by [`go_to_url`], [`replace_url`], and [`link!`](crate::link), so you
+/// only need it for things like `window.open`.
+pub fn browser_url(url: &str) -> String {
+    ROUTER_CONFIG.with(|config| {
+        let config = config.borrow();
+
+        match config.mode {
+            RoutingMode::History => add_base(&config.base_path, url),
+            RoutingMode::Hash => {
+                if url.starts_with('/') && !url.starts_with("//") {
+                    format!("#{}", url)
+
+                } else {
+                    // Relative URLs (like `?page=2`) are relative to the current route, not the browser's URL
+                    let current = current_url();
+                    let resolved = bindings::parse_url(&bindings::resolve_url(url, &current));
+
+                    if resolved.origin() == bindings::parse_url(&current).origin() {
+                        format!("#{}{}{}", resolved.pathname(), resolved.search(), resolved.hash())
+
+                    } else {
+                        url.to_string()
+                    }
+                }
+            },
+        }
+    })
+}
+
+// Converts the browser's URL into the URL which is used by the app
+fn current_url() -> String {
+    let href = bindings::current_url();
+    app_url(&href).unwrap_or(href)
+}
+
+// Converts a browser URL into the URL which is used by the app, it returns `None` if the URL is outside of the base path
+fn app_url(href: &str) -> Option<String> {
+    ROUTER_CONFIG.with(|config| {
+        let config = config.borrow();
+
+        match config.mode {
+            RoutingMode::History => {
+                if config.base_path.is_empty() {
+                    Some(href.to_string())
+
+                } else {
+                    let url = bindings::parse_url(href);
+                    url.set_pathname(strip_base(&config.base_path, &url.pathname())?);
+                    Some(url.href())
+                }
+            },
+            RoutingMode::Hash => {
+                let hash = bindings::parse_url(href).hash();
+
+                match hash_path(&hash) {
+                    Some(path) => Some(bindings::resolve_url(&path, href)),
+
+                    // The browser followed an ordinary anchor (like `#section`), so the app stays on the same route
+                    None => {
+                        // This is also called while the current route is being initialized
+                        let route = CURRENT_URL.with(|url| url.get().map(|url| url.as_mutable().get_cloned()));
+                        Some(route.unwrap_or_else(|| bindings::resolve_url("/", href)))
+                    },
+                }
+            },
+        }
+    })
+}
 
 
 // TODO inline ?
 fn change_url(mutable: &Mutable<String>) {
+    // This must be before the lock, because in hash mode it might read the current route
+    let new_url = current_url();
+
     let mut lock = mutable.lock_mut();
 
-    let new_url = String::from(bindings::current_url());
+    save_history_index();
+
+    HISTORY_STATE.with(|state| {
+        state.set(bindings::history_state_payload(&bindings::history_state()));
+    });
+
+    // TODO helper method for this
+    // TODO can this be made more efficient ?
+    if *lock != new_url {
+        *lock = new_url;
+    }
+}
+
+
+thread_local! {
+    static CURRENT_URL: RefCounter<MutableListener<String>> = RefCounter::new();
+
+    // The index of the history entry for CURRENT_URL, this is used to go back to it when navigation is blocked
+    static HISTORY_INDEX: Cell<u32> = const { Cell::new(0) };
+
+    // A traversal which was started by routing, the URL listener must not check the blockers for it
+    static TRAVERSAL: Cell<Option<Traversal>> = const { Cell::new(None) };
+
+    static HISTORY_STATE: Mutable<JsValue> = Mutable::new(bindings::history_state_payload(&bindings::history_state()));
+}
+
+fn save_history_index() {
+    let index = current_history_index().unwrap_or(0);
+    HISTORY_INDEX.with(|old| old.set(index));
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Traversal {
+    // The navigation was blocked, so it goes back to the entry with this index
+    Undo(u32),
+    // The blocked navigation was allowed, so it goes forward to the entry with this index
+    Redo(u32),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum PopState {
+    // The URL is the same as before, so nothing needs to be done
+    Ignore,
+    // Changes the URL without checking the blockers
+    Navigate,
+    // Checks the blockers before changing the URL
+    Check { old_index: u32, new_index: u32 },
+}
+
+// Decides what to do when the back or forward buttons are used
+fn pop_state(traversal: Option<Traversal>, old_index: u32, index: Option<u32>) -> PopState {
+    match traversal {
+        Some(Traversal::Undo(expected)) if index == Some(expected) => return PopState::Ignore,
+        Some(Traversal::Redo(expected)) if index == Some(expected) => return PopState::Navigate,
+        _ => {},
+    }
+
+    // Entries without an index weren't created by routing (for example `#fragment` links), so they are new entries
+    let new_index = index.unwrap_or(old_index + 1);
+
+    if new_index == old_index {
+        PopState::Navigate
+
+    } else {
+        PopState::Check { old_index, new_index }
+    }
+}
+
+fn traverse(traversal: Traversal, from: u32) {
+    let to = match traversal {
+        Traversal::Undo(to) => to,
+        Traversal::Redo(to) => to,
+    };
+
+    TRAVERSAL.with(|old| old.set(Some(traversal)));
+
+    bindings::go(to as i32 - from as i32);
+}
+
+fn navigate(url: &Mutable<String>) {
+    save_scroll();
+    change_url(url);
+    after_navigation(true);
+}
+
+
+// If the CURRENT_URL is initialized, then run the function `f`
+fn try_url<F>(f: F) where F: FnOnce(&Mutable<String>) {
+    CURRENT_URL.with(|url| {
+        if let Some(url) = &*url.try_borrow() {
+            f(url.as_mutable());
+        }
+    })
+}
+
+
+fn url_listener(url: Mutable<String>) -> EventListener {
+    let event = match current_mode() {
+        RoutingMode::History => "popstate",
+        RoutingMode::Hash => "hashchange",
+    };
+
+    WINDOW.with(move |window| {
+        EventListener::new(window, event, &EventOptions::default(), move |_| {
+            // The browser scrolls to the anchor, and the route stays the same
+            if current_mode() == RoutingMode::Hash && !is_route_fragment(&bindings::parse_url(&bindings::current_url()).hash()) {
+                return;
+            }
+
+            let index = current_history_index();
+            let old_index = HISTORY_INDEX.with(|index| index.get());
+            let traversal = TRAVERSAL.with(|traversal| traversal.take());
+
+            match pop_state(traversal, old_index, index) {
+                PopState::Ignore => {},
+                PopState::Navigate => {
+                    navigate(&url);
+                },
+                PopState::Check { old_index, new_index } => {
+                    if index.is_none() {
+                        let payload = bindings::history_state_payload(&bindings::history_state());
+                        bindings::replace_url(&bindings::current_url(), &new_history_state(new_index, &payload));
+                    }
+
+                    // In-page anchors (like `#section`) stay on the same route, so they aren't blocked
+                    if is_same_document(&url.get_cloned(), &current_url()) {
+                        navigate(&url);
+                        return;
+                    }
+
+                    // The browser has already changed the URL, so if it's blocked then it goes back to the previous entry
+                    match check_blockers(current_url) {
+                        BlockerCheck::Allowed => {
+                            navigate(&url);
+                        },
+                        BlockerCheck::Blocked => {
+                            traverse(Traversal::Undo(old_index), new_index);
+                        },
+                        BlockerCheck::Pending(allowed) => {
+                            traverse(Traversal::Undo(old_index), new_index);
+
+                            spawn_local(async move {
+                                // The user might have navigated somewhere else while it was waiting
+                                if allowed.await && HISTORY_INDEX.with(|index| index.get()) == old_index {
+                                    traverse(Traversal::Redo(new_index), old_index);
+                                }
+                            });
+                        },
+                    }
+                },
+            }
+        })
+    })
+}
+
+
+/// The current URL, which is returned by [`url_handle`].
+///
+/// The URL listener is shared, and it is kept alive as long as there is a `CurrentUrl`
+/// (or a [`Signal`] which was created from it). When they are all dropped, the listener is removed.
+#[derive(Debug)]
+pub struct CurrentUrl {
+    url: Mutable<String>,
+}
+
+impl CurrentUrl {
+    fn new() -> Self {
+        let url = CURRENT_URL.with(|url| {
+            let url = url.increment(|| {
+                // TODO can this be made more efficient ?
+                let url = Mutable::new(current_url());
+
+                init_history_index();
+                save_history_index();
+
+                let listener = url_listener(url.clone());
+
+                MutableListener::new(url, listener)
+            });
+
+            url.as_mutable().clone()
+        });
+
+        Self { url }
+    }
+
+    /// Returns a clone of the current URL.
+    #[inline]
+    pub fn get_cloned(&self) -> String {
+        self.url.get_cloned()
+    }
+
+    /// Returns a reference to the current URL.
+    #[inline]
+    pub fn lock_ref(&self) -> MutableLockRef<'_, String> {
+        self.url.lock_ref()
+    }
+
+    /// Returns a [`Signal`] of the current URL.
+    #[inline]
+    pub fn signal_cloned(&self) -> impl Signal<Item = String> {
+        UrlSignal {
+            signal: self.url.signal_cloned(),
+            _url: self.clone(),
+        }
+    }
+
+    /// Returns a [`Signal`] which calls the closure with a reference to the current URL.
+    #[inline]
+    pub fn signal_ref<A, F>(&self, f: F) -> impl Signal<Item = A> where F: FnMut(&String) -> A {
+        UrlSignal {
+            signal: self.url.signal_ref(f),
+            _url: self.clone(),
+        }
+    }
+}
+
+impl Clone for CurrentUrl {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for CurrentUrl {
+    fn drop(&mut self) {
+        CURRENT_URL.with(|url| {
+            url.decrement();
+        });
+    }
+}
+
+
+#[pin_project]
+#[derive(Debug)]
+#[must_use = "Signals do nothing unless polled"]
+struct UrlSignal<A> {
+    #[pin]
+    signal: A,
+    // This keeps the URL listener alive
+    _url: CurrentUrl,
+}
+
+impl<A> Signal for UrlSignal<A> where A: Signal {
+    type Item = A::Item;
+
+    #[inline]
+    fn poll_change(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.project().signal.poll_change(cx)
+    }
+}
+
+
+/// Returns the current URL.
+///
+/// The [`base_path`](RouterConfig::base_path) is removed from the URL, and in [`RoutingMode::Hash`]
+/// the fragment is used as the path of the URL.
+///
+/// ```rust
+/// routing::url().signal_ref(|url| Route::from_url(url))
+/// ```
+///
+/// The URL listener is kept alive forever, use [`url_handle`] if you want it to be removed when it isn't used.
+pub fn url() -> ReadOnlyMutable<String> {
+    let url = CurrentUrl::new();
+    let mutable = url.url.read_only();
+
+    // The ReadOnlyMutable can't decrement the ref count, so the listener is never removed
+    std::mem::forget(url);
+
+    mutable
+}
+
+/// The same as [`url`], except it returns a [`CurrentUrl`].
+///
+/// When every [`CurrentUrl`] (and every [`Signal`] created from it) is dropped, the URL listener is removed.
+#[inline]
+pub fn url_handle() -> CurrentUrl {
+    CurrentUrl::new()
+}
+
+
+type AsyncBlocker = Box<dyn FnMut(&str) -> Pin<Box<dyn Future<Output = bool>>>>;
+
+enum Blocker {
+    Sync(Box<dyn FnMut(&str) -> bool>),
+    Async(AsyncBlocker),
+}
+
+thread_local! {
+    static BLOCKERS: RefCell<Vec<(usize, Rc<RefCell<Blocker>>)>> = const { RefCell::new(vec![]) };
+    static BLOCKER_ID: Cell<usize> = const { Cell::new(0) };
+}
+
+enum BlockerCheck {
+    Allowed,
+    Blocked,
+    Pending(Pin<Box<dyn Future<Output = bool>>>),
+}
+
+fn check_blocker(blocker: &RefCell<Blocker>, new_url: &str) -> BlockerCheck {
+    match &mut *blocker.borrow_mut() {
+        Blocker::Sync(f) => if f(new_url) {
+            BlockerCheck::Allowed
+
+        } else {
+            BlockerCheck::Blocked
+        },
+        Blocker::Async(f) => BlockerCheck::Pending(f(new_url)),
+    }
+}
+
+// The blockers run in the same order that they were created
+fn check_blockers<F>(new_url: F) -> BlockerCheck where F: FnOnce() -> String {
+    // The blockers are cloned so that they can add or remove blockers
+    let blockers = BLOCKERS.with(|blockers| {
+        blockers.borrow().iter().map(|(_, blocker)| blocker.clone()).collect::<Vec<_>>()
+    });
+
+    if blockers.is_empty() {
+        return BlockerCheck::Allowed;
+    }
+
+    let new_url = new_url();
+
+    let mut blockers = blockers.into_iter();
+
+    // The sync blockers run immediately, until it reaches an async blocker
+    while let Some(blocker) = blockers.next() {
+        match check_blocker(&blocker, &new_url) {
+            BlockerCheck::Allowed => {},
+            BlockerCheck::Blocked => {
+                return BlockerCheck::Blocked;
+            },
+            BlockerCheck::Pending(allowed) => {
+                let rest = blockers.collect::<Vec<_>>();
+
+                return BlockerCheck::Pending(Box::pin(async move {
+                    if !allowed.await {
+                        return false;
+                    }
+
+                    // The remaining blockers run one at a time, so only one confirmation is shown at once
+                    for blocker in rest {
+                        let allowed = match check_blocker(&blocker, &new_url) {
+                            BlockerCheck::Allowed => true,
+                            BlockerCheck::Blocked => false,
+                            BlockerCheck::Pending(allowed) => allowed.await,
+                        };
+
+                        if !allowed {
+                            return false;
+                        }
+                    }
+
+                    true
+                }));
+            },
+        }
+    }
+
+    BlockerCheck::Allowed
+}
+
+fn register_blocker(blocker: Blocker) -> usize {
+    let id = BLOCKER_ID.with(|id| {
+        let value = id.get();
+        id.set(value + 1);
+        value
+    });
+
+    BLOCKERS.with(|blockers| {
+        blockers.borrow_mut().push((id, Rc::new(RefCell::new(blocker))));
+    });
+
+    id
+}
+
+fn unregister_blocker(id: usize) {
+    BLOCKERS.with(|blockers| {
+        blockers.borrow_mut().retain(|(old, _)| *old != id);
+    });
+}
+
+fn add_blocker(blocker: Blocker) -> NavigationBlocker {
+    NavigationBlocker {
+        id: register_blocker(blocker),
+        // The URL listener is needed for blocking the back and forward buttons
+        _url: url_handle(),
+        _unload: None,
+    }
+}
+
+
+/// A navigation blocker which was created with [`block_navigation`] or [`block_navigation_async`].
+///
+/// When it is dropped or discarded, the navigation is no longer blocked.
+#[must_use]
+pub struct NavigationBlocker {
+    id: usize,
+    _url: CurrentUrl,
+    _unload: Option<DiscardOnDrop<EventListener>>,
+}
+
+impl NavigationBlocker {
+    /// Also blocks the user from closing or reloading the tab.
+    ///
+    /// Browsers don't allow custom messages for this, so the browser will show its own confirmation dialog.
+    pub fn before_unload(mut self) -> Self {
+        if self._unload.is_none() {
+            self._unload = Some(DiscardOnDrop::new(WINDOW.with(|window| {
+                EventListener::new(window, "beforeunload", &EventOptions::preventable(), |e| {
+                    bindings::prevent_unload(e);
+                })
+            })));
+        }
+
+        self
+    }
+}
+
+impl Drop for NavigationBlocker {
+    fn drop(&mut self) {
+        unregister_blocker(self.id);
+    }
+}
+
+impl Discard for NavigationBlocker {
+    #[inline]
+    fn discard(self) {
+        // Everything is cleaned up when it is dropped
+    }
+}
+
+/// Blocks navigation, which is useful for preventing the user from losing unsaved changes.
+///
+/// Before [`go_to_url`] changes the URL, it calls the closure with the new URL. If the closure
+/// returns `false` then the navigation is cancelled. This also applies to [`link!`](crate::link)
+/// and [`on_click_go_to_url!`](crate::on_click_go_to_url).
+///
+/// It also applies to the browser's back and forward buttons. In that case the browser has already
+/// changed the URL, so if the navigation is cancelled then it goes back to the previous history entry.
+///
+/// If there are multiple blockers, they run in the same order that they were created.
+///
+/// ```rust
+/// let blocker = block_navigation(|_new_url| {
+///     window().confirm_with_message("You have unsaved changes, are you sure you want to leave?").unwrap()
+/// }).before_unload();
+///
+/// // When the changes have been saved
+/// blocker.discard();
+/// ```
+///
+/// The navigation is only blocked while the [`NavigationBlocker`] exists, so you must keep it alive.
+pub fn block_navigation<F>(f: F) -> NavigationBlocker where F: FnMut(&str) -> bool + 'static {
+    add_blocker(Blocker::Sync(Box::new(f)))
+}
+
+/// Similar to [`block_navigation`], except the closure returns a [`Future`].
+///
+/// This is useful for showing a custom confirmation dialog. The navigation happens after the
+/// `Future` resolves to `true`.
+///
+/// While the `Future` is waiting, the back and forward buttons are undone, and then they are
+/// redone if the `Future` resolves to `true`.
+pub fn block_navigation_async<F, A>(mut f: F) -> NavigationBlocker
+    where F: FnMut(&str) -> A + 'static,
+          A: Future<Output = bool> + 'static {
+    add_blocker(Blocker::Async(Box::new(move |url| Box::pin(f(url)))))
+}
+
+
+/// Update the current route by adding a new entry to the history.
+///
+/// If the navigation is blocked by [`block_navigation`] then the URL isn't changed.
+#[inline]
+#[track_caller]
+pub fn go_to_url(new_url: &str) {
+    go_to_url_with_state(new_url, JsValue::NULL);
+}
+
+/// Same as [`go_to_url`], except it also attaches some state to the new history entry.
+///
+/// The state can be accessed with [`history_state`] and [`history_state_signal`]. When the user
+/// uses the back and forward buttons, the state of that history entry is restored.
+///
+/// This is useful for things like the selected tab, or a modal which should close when the user goes back.
+///
+/// The state is copied with the [structured clone algorithm](https://developer.mozilla.org/en-US/docs/Web/API/Web_Workers_API/Structured_clone_algorithm).
+/// To store Rust values you can convert them into a [`JsValue`], for example with [serde-wasm-bindgen](https://docs.rs/serde-wasm-bindgen).
+#[track_caller]
+pub fn go_to_url_with_state(new_url: &str, state: JsValue) {
+    let browser_url = browser_url(new_url);
+
+    match check_blockers(|| bindings::resolve_url(new_url, &current_url())) {
+        BlockerCheck::Allowed => {
+            push_url(&browser_url, &state);
+        },
+        BlockerCheck::Blocked => {},
+        BlockerCheck::Pending(allowed) => {
+            spawn_local(async move {
+                if allowed.await {
+                    push_url(&browser_url, &state);
+                }
+            });
+        },
+    }
+}
+
+fn push_url(browser_url: &str, state: &JsValue) {
+    save_scroll();
+
+    let index = init_history_index() + 1;
+
+    // TODO intern ?
+    bindings::go_to_url(browser_url, &new_history_state(index, state));
+
+    try_url(change_url);
+
+    after_navigation(false);
+}
+
+/// Update the current route by replacing the history.
+/// Use this very sparingly as this break the back button.
+///
+/// To let the user go back to the current route, use [`go_to_url`] instead.
+#[inline]
+#[track_caller]
+pub fn replace_url(new_url: &str) {
+    // The key and index are kept so that the scroll position is still restored, but the state is cleared
+    replace_url_with_state(new_url, JsValue::NULL);
+}
+
+/// Same as [`replace_url`], except it also replaces the state of the current history entry.
+///
+/// See [`go_to_url_with_state`] for more details.
+#[track_caller]
+pub fn replace_url_with_state(new_url: &str, state: JsValue) {
+    let old_state = bindings::history_state();
+    let key = bindings::history_state_key(&old_state);
+    let index = bindings::history_state_index(&old_state);
+
+    // TODO intern ?
+    bindings::replace_url(&browser_url(new_url), &bindings::create_history_state(key, index, &state));
+
+    try_url(change_url);
+}
+
+/// Returns the state of the current history entry.
+///
+/// If the history entry doesn't have any state, then it returns `null`.
+///
+/// See [`go_to_url_with_state`] for more details.
+#[inline]
+pub fn history_state() -> JsValue {
+    bindings::history_state_payload(&bindings::history_state())
+}
+
+/// Returns a [`Signal`] of the state of the current history entry.
+///
+/// It changes when the URL changes, and also when the user uses the back and forward buttons.
+///
+/// See [`go_to_url_with_state`] for more details.
+pub fn history_state_signal() -> impl Signal<Item = JsValue> {
+    UrlSignal {
+        signal: HISTORY_STATE.with(|state| state.signal_cloned()),
+        _url: url_handle(),
+    }
+}
+
+
+/// A typed route, which can be converted to and from a URL path.
+///
+/// Normally you would use the [`routes!`](crate::routes) macro to implement this, but
+/// you can also implement it manually.
+pub trait Route: Sized {
+    /// Converts a URL path (such as `/users/5`) into a route.
+    ///
+    /// The path is percent-encoded, exactly as it is in the browser's URL.
+    ///
+    /// Returns `None` if the path doesn't match any route.
+    fn match_path(path: &str) -> Option<Self>;
+
+    /// Converts the route into a percent-encoded URL path.
+    fn to_path(&self) -> String;
+
+    /// The part of the path which is matched by this route, not including any nested routes.
+    ///
+    /// This is used by [`route_outlet`](crate::DomBuilder::route_outlet) to decide whether it needs to re-render.
+    #[inline]
+    fn matched_path(&self) -> String {
+        self.to_path()
+    }
+
+    /// Converts the route into a URL which can be used with [`go_to_url`] and [`link!`](crate::link).
+    #[inline]
+    fn to_url(&self) -> String {
+        self.to_path()
+    }
+}
+
+
+/// The parameters which were matched by a route pattern.
+///
+/// In the pattern `/users/:id/posts/*rest` there are two parameters, `id` and `rest`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RouteParams {
+    params: HashMap<String, String>,
+}
+
+impl RouteParams {
+    /// Returns the percent-decoded value of the parameter.
+    #[inline]
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(|value| value.as_str())
+    }
+
+    /// Parses the parameter with [`FromStr`].
+    ///
+    /// Returns `None` if the parameter doesn't exist or if it fails to parse.
+    #[inline]
+    pub fn parse<A>(&self, name: &str) -> Option<A> where A: FromStr {
+        self.get(name)?.parse().ok()
+    }
+}
+
+
+/// Matches a route pattern against a URL path.
+///
+/// The pattern is a list of segments separated by `/`:
+///
+/// * `users` matches the segment `users` exactly.
+///
+/// * `:id` matches any non-empty segment, and stores it in the `id` parameter.
+///
+/// * `*rest` matches the rest of the path (including `/`), and stores it in the `rest` parameter.
+///   It must be the last segment.
+///
+/// A single trailing `/` in the path is ignored, so `/users/5/` matches `/users/:id`.
+///
+/// ```rust
+/// let params = match_route("/users/:id", "/users/5").unwrap();
+/// assert_eq!(params.parse::<u32>("id"), Some(5));
+/// ```
+#[inline]
+pub fn match_route(pattern: &str, path: &str) -> Option<RouteParams> {
+    let (params, rest) = __internal::match_prefix(pattern, path)?;
+
+    if rest.is_empty() || rest == "/" {
+        Some(params)
+
+    } else {
+        None
+    }
+}
+
+
+/// Returns a [`Signal`] of the current route.
+///
+/// Whenever the URL changes, it uses [`Route::match_path`] to convert the URL into a route.
+///
+/// ```rust
+/// route_signal::<AppRoute>().map(|route| {
+///     match route {
+///         Some(AppRoute::Home) => ...,
+///         Some(AppRoute::User { id }) => ...,
+///         None => ...,
+///     }
+/// })
+/// ```
+#[inline]
+pub fn route_signal<A>() -> impl Signal<Item = Option<A>> where A: Route {
+    url_handle().signal_ref(|url| A::match_path(&bindings::url_path(url)))
+}
+
+
+struct OutletRoute<A> {
+    matched_path: Option<String>,
+    route: Option<A>,
+}
+
+impl<A> PartialEq for OutletRoute<A> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.matched_path == other.matched_path
+    }
+}
+
+pub(crate) fn outlet_signal<A, B, F>(signal: B, mut f: F) -> impl Signal<Item = Option<Dom>>
+    where A: Route,
+          B: Signal<Item = Option<A>>,
+          F: FnMut(Option<A>) -> Option<Dom> {
+    signal
+        .map(|route| OutletRoute {
+            matched_path: route.as_ref().map(|route| route.matched_path()),
+            route,
+        })
+        // Nested routes don't change the matched path, so they don't re-render the outlet
+        .dedupe_map(move |outlet| f(outlet.route.take()))
+}
+
+
+fn decode_query(value: &str) -> Option<String> {
+    __internal::decode(&value.replace('+', " "))
+}
+
+fn encode_query(value: &str, output: &mut String) {
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                output.push(char::from(byte));
+            },
+            _ => {
+                output.push_str(&format!("%{:02X}", byte));
+            },
+        }
+    }
+}
+
+fn get_query_param(query: &str, name: &str) -> Option<String> {
+    let query = query.strip_prefix('?').unwrap_or(query);
+
+    query.split('&').find_map(|param| {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+
+        if decode_query(key)? == name {
+            decode_query(value)
+
+        } else {
+            None
+        }
+    })
+}
+
+// Changes a single parameter, all of the other parameters are kept as-is
+fn set_query_param(query: &str, name: &str, value: Option<&str>) -> String {
+    let query = query.strip_prefix('?').unwrap_or(query);
+
+    let mut output = String::new();
+    let mut value = value;
+
+    let push = |output: &mut String, value: &str| {
+        if !output.is_empty() {
+            output.push('&');
+        }
+
+        encode_query(name, output);
+        output.push('=');
+        encode_query(value, output);
+    };
+
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let key = param.split('=').next().unwrap_or(param);
+
+        if decode_query(key).as_deref() == Some(name) {
+            // The first matching parameter is replaced, the rest are removed
+            if let Some(value) = value.take() {
+                push(&mut output, value);
+            }
+
+        } else {
+            if !output.is_empty() {
+                output.push('&');
+            }
+
+            output.push_str(param);
+        }
+    }
+
+    if let Some(value) = value {
+        push(&mut output, value);
+    }
+
+    output
+}
+
+// Whether the URL needs to be changed, a missing (or invalid) parameter is the same as `default`
+fn is_query_param_changed<A>(query: &str, name: &str, value: &A, default: &A) -> bool where A: FromStr + PartialEq {
+    match get_query_param(query, name).and_then(|current| current.parse::<A>().ok()) {
+        Some(current) => current != *value,
+        None => value != default,
+    }
+}
+
+fn query_param_url(name: &str, value: Option<&str>) -> String {
+    let url = bindings::parse_url(&current_url());
+
+    let query = set_query_param(&url.search(), name, value);
+
+    if query.is_empty() {
+        format!("{}{}", url.pathname(), url.hash())
+
+    } else {
+        format!("{}?{}{}", url.pathname(), query, url.hash())
+    }
+}
+
+/// The state of a route which is rendered with [`route_outlet_async`](crate::DomBuilder::route_outlet_async).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteLoad<A, B> {
+    /// The data for the route is being loaded.
+    Loading(A),
+
+    /// The data for the route has been loaded.
+    Loaded(A, B),
+}
+
+thread_local! {
+    static PENDING_LOADS: Mutable<usize> = Mutable::new(0);
+}
+
+/// Returns a [`Signal`] which is `true` while a [`route_outlet_async`](crate::DomBuilder::route_outlet_async)
+/// is loading data.
+///
+/// This is useful for showing a global progress bar.
+pub fn navigation_pending_signal() -> impl Signal<Item = bool> {
+    PENDING_LOADS.with(|pending| pending.signal_ref(|pending| *pending > 0)).dedupe()
+}
+
+struct PendingLoad;
+
+impl PendingLoad {
+    fn new() -> Self {
+        PENDING_LOADS.with(|pending| *pending.lock_mut() += 1);
+        Self
+    }
+}
+
+impl Drop for PendingLoad {
+    fn drop(&mut self) {
+        PENDING_LOADS.with(|pending| *pending.lock_mut() -= 1);
+    }
+}
+
+struct Load<A, B> {
+    route: A,
+    receiver: oneshot::Receiver<B>,
+    // Dropping the handle cancels the load
+    _handle: DiscardOnDrop<CancelableFutureHandle>,
+    _pending: PendingLoad,
+}
+
+#[pin_project]
+struct LoaderSignal<A, B, C, L, F> {
+    #[pin]
+    signal: Option<A>,
+    matched_path: Option<Option<String>>,
+    load: Option<Load<B, C>>,
+    loader: L,
+    render: F,
+}
+
+impl<A, B, C, D, L, F> Signal for LoaderSignal<A, B, C, L, F>
+    where A: Signal<Item = Option<B>>,
+          B: Route + Clone,
+          C: 'static,
+          D: Future<Output = C> + 'static,
+          L: FnMut(&B) -> D,
+          F: FnMut(Option<RouteLoad<B, C>>) -> Option<Dom> {
+    type Item = Option<Dom>;
+
+    fn poll_change(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+
+        let mut changed = None;
+
+        let is_done = loop {
+            match this.signal.as_mut().as_pin_mut().map(|signal| signal.poll_change(cx)) {
+                Some(Poll::Ready(Some(route))) => {
+                    let matched_path = route.as_ref().map(|route| route.matched_path());
+
+                    // Nested routes don't change the matched path, so they don't reload the route
+                    if this.matched_path.as_ref() != Some(&matched_path) {
+                        *this.matched_path = Some(matched_path);
+                        changed = Some(route);
+                    }
+                },
+                Some(Poll::Ready(None)) => {
+                    this.signal.set(None);
+                    break true;
+                },
+                Some(Poll::Pending) => {
+                    break false;
+                },
+                None => {
+                    break true;
+                },
+            }
+        };
+
+        if let Some(route) = changed {
+            // Cancels the previous load
+            *this.load = None;
+
+            return Poll::Ready(Some(match route {
+                Some(route) => {
+                    let future = (this.loader)(&route);
+                    let (sender, receiver) = oneshot::channel();
+
+                    *this.load = Some(Load {
+                        route: route.clone(),
+                        receiver,
+                        _handle: spawn_future(async move {
+                            let _ = sender.send(future.await);
+                        }),
+                        _pending: PendingLoad::new(),
+                    });
+
+                    (this.render)(Some(RouteLoad::Loading(route)))
+                },
+                None => (this.render)(None),
+            }));
+        }
+
+        if let Some(load) = this.load.as_mut() {
+            match load.receiver.poll_unpin(cx) {
+                Poll::Ready(Ok(data)) => {
+                    let load = this.load.take().unwrap();
+                    return Poll::Ready(Some((this.render)(Some(RouteLoad::Loaded(load.route, data)))));
+                },
+                Poll::Ready(Err(_)) => {
+                    *this.load = None;
+                },
+                Poll::Pending => {
+                    return Poll::Pending;
+                },
+            }
+        }
+
+        if is_done {
+            Poll::Ready(None)
+
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+pub(crate) fn loader_outlet_signal<A, B, C, D, L, F>(signal: A, loader: L, render: F) -> impl Signal<Item = Option<Dom>>
+    where A: Signal<Item = Option<B>>,
+          B: Route + Clone,
+          C: 'static,
+          D: Future<Output = C> + 'static,
+          L: FnMut(&B) -> D,
+          F: FnMut(Option<RouteLoad<B, C>>) -> Option<Dom> {
+    LoaderSignal {
+        signal: Some(signal),
+        matched_path: None,
+        load: None,
+        loader,
+        render,
+    }
+}
+
+
+/// Returns a [`Signal`] of a query parameter in the current URL.
+///
+/// The value is parsed with [`FromStr`]. If the parameter doesn't exist, or if it fails to parse, then it is `None`.
+///
+/// ```rust
+/// // When the URL is `/search?page=5` this is `Some(5)`
+/// query_param_signal::<u32>("page")
+/// ```
+pub fn query_param_signal<A>(name: &str) -> impl Signal<Item = Option<A>> where A: FromStr {
+    let name = name.to_string();
+
+    url_handle()
+        .signal_ref(move |url| get_query_param(&bindings::parse_url(url).search(), &name))
+        .dedupe_cloned()
+        .map(|value| value.and_then(|value| value.parse().ok()))
+}
+
+/// Changes a single query parameter in the current URL, by using [`replace_url`].
+///
+/// If the value is `None` then the parameter is removed. The other query parameters are not changed.
+pub fn replace_query_param<A>(name: &str, value: Option<A>) where A: Display {
+    replace_url(&query_param_url(name, value.map(|value| value.to_string()).as_deref()));
+}
+
+/// Changes a single query parameter in the current URL, by using [`go_to_url`].
+///
+/// This is the same as [`replace_query_param`], except it adds a new history entry.
+pub fn go_to_query_param<A>(name: &str, value: Option<A>) where A: Display {
+    go_to_url(&query_param_url(name, value.map(|value| value.to_string()).as_deref()));
+}
+
+/// Binds a [`Mutable`] to a query parameter, in both directions.
+///
+/// When the query parameter changes, the `Mutable` is updated. And when the `Mutable` changes,
+/// the query parameter is updated with [`replace_query_param`]. If the URL already has the same value then
+/// it isn't changed, so binding the parameter doesn't replace the history entry.
+///
+/// If the parameter doesn't exist (or it fails to parse) then the `Mutable` is set to `default`. And when
+/// the `Mutable` is equal to `default` then the parameter is removed from the URL.
+///
+/// It returns a [`Future`], which should be passed to [`DomBuilder::future`]:
+///
+/// ```rust
+/// let page = Mutable::new(1);
+///
+/// html!("div", {
+///     .future(bind_query_param("page", page.clone(), 1))
+///     .text_signal(page.signal().map(|page| format!("Page {}", page)))
+/// })
+/// ```
+pub fn bind_query_param<A>(name: &str, mutable: Mutable<A>, default: A) -> impl Future<Output = ()>
+    where A: FromStr + Display + PartialEq + Clone + 'static {
+
+    let from_url = {
+        let mutable = mutable.clone();
+        let default = default.clone();
+
+        query_param_signal::<A>(name).for_each(move |value| {
+            mutable.set_neq(value.unwrap_or_else(|| default.clone()));
+            ready(())
+        })
+    };
+
+    let name = name.to_string();
+
+    let to_url = mutable.signal_cloned().for_each(move |value| {
+        // This avoids replacing the history entry when the URL already has the value
+        if is_query_param_changed(&bindings::parse_url(&current_url()).search(), &name, &value, &default) {
+            if value == default {
+                replace_query_param::<A>(&name, None);
+
+            } else {
+                replace_query_param(&name, Some(value));
+            }
+        }
+
+        ready(())
+    });
+
+    // The URL is first, so the Mutable is updated before it changes the URL
+    join(from_url, to_url).map(|_| ())
+}
+
+
+/// Specifies how [`is_active_signal`] and [`active_link!`](crate::active_link) match the current URL.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Default)]
+pub struct LinkMatch {
+    /// If `true` then the link is also active for nested paths, so `/users` is active for `/users/5`.
+    ///
+    /// If `false` then the path must match exactly.
+    pub prefix: bool,
+
+    /// If `true` then the query parameters of the link must also be in the current URL.
+    ///
+    /// If `false` then the query string is ignored.
+    pub query: bool,
+}
+
+impl LinkMatch {
+    /// The path must match exactly, the query string is ignored.
+    #[inline]
+    pub fn exact() -> Self {
+        Self {
+            prefix: false,
+            query: false,
+        }
+    }
+
+    /// The path must start with the link's path, the query string is ignored.
+    #[inline]
+    pub fn prefix() -> Self {
+        Self {
+            prefix: true,
+            query: false,
+        }
+    }
+
+    /// The query parameters of the link must also be in the current URL.
+    #[inline]
+    pub fn with_query(mut self) -> Self {
+        self.query = true;
+        self
+    }
+}
+
+// Splits a URL into the origin, path, and query string, the fragment is ignored
+fn split_url(url: &str) -> (&str, &str, &str) {
+    let url = url.split('#').next().unwrap_or(url);
+
+    let (url, query) = url.split_once('?').unwrap_or((url, ""));
+
+    let path_start = url.find("://")
+        .map(|index| url[index + 3..].find('/').map(|path| index + 3 + path).unwrap_or(url.len()))
+        .unwrap_or(0);
+
+    let (origin, path) = url.split_at(path_start);
+
+    let path = if path.len() > 1 {
+        path.strip_suffix('/').unwrap_or(path)
+
+    } else {
+        "/"
+    };
+
+    (origin, path, query)
+}
+
+fn link_matches(link: &str, current: &str, options: LinkMatch) -> bool {
+    let (link_origin, link_path, link_query) = split_url(link);
+    let (current_origin, current_path, current_query) = split_url(current);
+
+    if link_origin != current_origin {
+        return false;
+    }
+
+    let path_matches = if options.prefix {
+        link_path == "/" || current_path.strip_prefix(link_path).map(|rest| rest.is_empty() || rest.starts_with('/')).unwrap_or(false)
+
+    } else {
+        link_path == current_path
+    };
+
+    path_matches && (!options.query || link_query.split('&').filter(|param| !param.is_empty()).all(|param| {
+        current_query.split('&').any(|current| current == param)
+    }))
+}
+
+/// Returns a [`Signal`] which is `true` if the URL matches the current URL.
+///
+/// This is useful for highlighting the current page in a navigation menu, see [`active_link!`](crate::active_link).
+pub fn is_active_signal(url: &str, options: LinkMatch) -> impl Signal<Item = bool> {
+    let url = url.to_string();
+
+    url_handle()
+        .signal_ref(move |current| link_matches(&bindings::resolve_url(&url, current), current, options))
+        .dedupe()
+}
+
 
-    // TODO helper method for this
-    // TODO can this be made more efficient ?
-    if *lock != new_url {
-        *lock = new_url;
+#[doc(hidden)]
+pub mod __internal {
+    use futures_signals::signal::{Signal, SignalExt};
+    use wasm_bindgen::JsCast;
+    use web_sys::{Element, MouseEvent};
+    use crate::bindings;
+    use crate::traits::StaticEvent;
+    use super::{Route, RouteParams, LinkMatch, RoutingMode, is_active_signal, is_route_fragment, is_same_document, browser_url, app_url, current_mode, go_to_url};
+
+
+    /// A click event which falls back to the browser's behavior when the click isn't a normal click.
+    #[derive(Debug)]
+    pub struct LinkClick {
+        event: MouseEvent,
     }
-}
 
+    impl StaticEvent for LinkClick {
+        const EVENT_TYPE: &'static str = "click";
 
-thread_local! {
-    static CURRENT_URL: RefCounter<MutableListener<String>> = RefCounter::new();
-}
+        #[inline]
+        fn unchecked_from_event(event: web_sys::Event) -> Self {
+            Self {
+                event: event.unchecked_into(),
+            }
+        }
+    }
 
+    impl LinkClick {
+        // Whether the browser should handle the click, such as ctrl+click for opening a new tab
+        fn is_native(&self, link: &Element) -> bool {
+            let event = &self.event;
 
-// If the CURRENT_URL is initialized, then run the function `f`
-fn try_url<F>(f: F) where F: FnOnce(&Mutable<String>) {
-    CURRENT_URL.with(|url| {
-        if let Some(url) = &*url.try_borrow() {
-            f(url.as_mutable());
+            event.default_prevented() ||
+            event.button() != 0 ||
+            event.ctrl_key() ||
+            event.meta_key() ||
+            event.shift_key() ||
+            event.alt_key() ||
+            link.has_attribute("download") ||
+            link.get_attribute("target").map(|target| !target.is_empty() && target != "_self").unwrap_or(false)
         }
-    })
-}
 
+        fn is_same_origin(href: &str) -> bool {
+            let url = bindings::parse_url(href);
+            let current = bindings::parse_url(&bindings::current_url());
+            url.protocol() == current.protocol() && url.origin() == current.origin()
+        }
 
-pub fn url() -> ReadOnlyMutable<String> {
-    CURRENT_URL.with(|url| {
-        // TODO this needs to call decrement to clean up the listener
-        let url = url.increment(|| {
-            // TODO can this be made more efficient ?
-            let url = Mutable::new(String::from(bindings::current_url()));
-
-            let listener = {
-                let url = url.clone();
-
-                WINDOW.with(move |window| {
-                    EventListener::new(window, "popstate", &EventOptions::default(), move |_| {
-                        change_url(&url);
-                    })
-                })
+        pub fn go_to_url(&self, url: &str) {
+            let link = self.event.current_target().and_then(|target| target.dyn_into::<Element>().ok());
+
+            if let Some(link) = link {
+                if self.is_native(&link) {
+                    return;
+                }
+            }
+
+            if !Self::is_same_origin(&bindings::resolve_url(&browser_url(url), &bindings::current_url())) {
+                return;
+            }
+
+            self.event.prevent_default();
+            go_to_url(url);
+        }
+
+        pub fn intercept(&self) {
+            let link = self.event.target()
+                .and_then(|target| target.dyn_into::<Element>().ok())
+                .and_then(|target| target.closest("a[href]").ok().flatten());
+
+            let link = match link {
+                Some(link) => link,
+                None => return,
             };
 
-            MutableListener::new(url, listener)
-        });
+            if self.is_native(&link) {
+                return;
+            }
 
-        url.as_mutable().read_only()
-    })
-}
+            let current = bindings::current_url();
+            let href = bindings::resolve_url(&link.get_attribute("href").unwrap_or_default(), &current);
 
+            if !Self::is_same_origin(&href) {
+                return;
+            }
 
-/// Update the current route by adding a new entry to the history.
-#[inline]
-#[track_caller]
-pub fn go_to_url(new_url: &str) {
-    // TODO intern ?
-    bindings::go_to_url(new_url);
+            let is_same_document = is_same_document(&href, &current);
 
-    try_url(change_url);
-}
+            match current_mode() {
+                RoutingMode::History => {
+                    // In-page anchors (like `#section`) are handled by the browser, so that it scrolls to them
+                    if is_same_document && href.contains('#') {
+                        return;
+                    }
+                },
+                RoutingMode::Hash => {
+                    // In hash mode the link must be for the same document
+                    if !is_same_document {
+                        return;
+                    }
 
-/// Update the current route by replacing the history.
-/// Use this very sparingly as this break the back button.
-///
-/// To let the user go back to the current route, use [`go_to_url`] instead.
-#[inline]
-#[track_caller]
-pub fn replace_url(new_url: &str) {
-    // TODO intern ?
-    bindings::replace_url(new_url);
+                    // Ordinary `#section` links are handled by the browser
+                    if !is_route_fragment(&bindings::parse_url(&href).hash()) {
+                        return;
+                    }
+                },
+            }
 
-    try_url(change_url);
+            let url = match app_url(&href) {
+                Some(url) => bindings::parse_url(&url),
+                None => return,
+            };
+
+            self.event.prevent_default();
+            go_to_url(&format!("{}{}{}", url.pathname(), url.search(), url.hash()));
+        }
+    }
+
+
+    #[inline]
+    pub fn aria_current_signal(url: &str, options: LinkMatch) -> impl Signal<Item = Option<&'static str>> {
+        is_active_signal(url, options).map(|active| if active { Some("page") } else { None })
+    }
+
+
+    fn hex(byte: u8) -> Option<u8> {
+        match byte {
+            b'0'..=b'9' => Some(byte - b'0'),
+            b'a'..=b'f' => Some(byte - b'a' + 10),
+            b'A'..=b'F' => Some(byte - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn decode(value: &str) -> Option<String> {
+        let bytes = value.as_bytes();
+        let mut output = Vec::with_capacity(bytes.len());
+        let mut index = 0;
+
+        while index < bytes.len() {
+            let byte = bytes[index];
+
+            if byte == b'%' {
+                let high = hex(*bytes.get(index + 1)?)?;
+                let low = hex(*bytes.get(index + 2)?)?;
+                output.push((high << 4) | low);
+                index += 3;
+
+            } else {
+                output.push(byte);
+                index += 1;
+            }
+        }
+
+        String::from_utf8(output).ok()
+    }
+
+    pub(crate) fn encode(value: &str, output: &mut String) {
+        for byte in value.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' |
+                b'-' | b'.' | b'_' | b'~' | b'!' | b'$' | b'&' | b'\'' |
+                b'(' | b')' | b'*' | b'+' | b',' | b';' | b'=' | b':' | b'@' => {
+                    output.push(char::from(byte));
+                },
+                _ => {
+                    output.push_str(&format!("%{:02X}", byte));
+                },
+            }
+        }
+    }
+
+
+    /// Matches the beginning of `path` with `pattern`.
+    ///
+    /// Returns the parameters and the unmatched part of the path, which is either empty or starts with `/`.
+    pub fn match_prefix<'a>(pattern: &str, path: &'a str) -> Option<(RouteParams, &'a str)> {
+        let mut params = RouteParams::default();
+        let mut rest = path;
+
+        for segment in pattern.split('/').filter(|segment| !segment.is_empty()) {
+            let remaining = rest.strip_prefix('/').unwrap_or(rest);
+
+            if let Some(name) = segment.strip_prefix('*') {
+                params.params.insert(name.to_string(), decode(remaining)?);
+                rest = "";
+                break;
+            }
+
+            let (value, next) = match remaining.find('/') {
+                Some(index) => remaining.split_at(index),
+                None => (remaining, ""),
+            };
+
+            if value.is_empty() {
+                return None;
+            }
+
+            if let Some(name) = segment.strip_prefix(':') {
+                params.params.insert(name.to_string(), decode(value)?);
+
+            } else if value != segment {
+                return None;
+            }
+
+            rest = next;
+        }
+
+        Some((params, rest))
+    }
+
+    /// Matches `path` with `pattern`, the unmatched part of the path can be consumed by nested routes.
+    pub fn match_variant<A, F>(pattern: &str, path: &str, f: F) -> Option<A> where F: FnOnce(&RouteParams, &mut &str) -> Option<A> {
+        let (params, mut rest) = match_prefix(pattern, path)?;
+
+        let route = f(&params, &mut rest)?;
+
+        if rest.is_empty() || rest == "/" {
+            Some(route)
+
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    pub fn match_nested<A>(rest: &mut &str) -> Option<A> where A: Route {
+        let path = std::mem::take(rest);
+
+        if path.is_empty() {
+            A::match_path("/")
+
+        } else {
+            A::match_path(path)
+        }
+    }
+
+
+    pub enum RouteField<'a> {
+        Param(&'a str, String),
+        Nested(String),
+        Skip,
+    }
+
+    /// Fills in the parameters of `pattern`, this is the inverse of [`match_variant`].
+    pub fn format_route(pattern: &str, fields: &[RouteField]) -> String {
+        let mut output = String::new();
+
+        let lookup = |name: &str| {
+            fields.iter()
+                .find_map(|field| match field {
+                    RouteField::Param(key, value) if *key == name => Some(value.as_str()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("Missing route parameter {}", name))
+        };
+
+        for segment in pattern.split('/').filter(|segment| !segment.is_empty()) {
+            output.push('/');
+
+            if let Some(name) = segment.strip_prefix('*') {
+                let mut parts = lookup(name).split('/').peekable();
+
+                while let Some(part) = parts.next() {
+                    encode(part, &mut output);
+
+                    if parts.peek().is_some() {
+                        output.push('/');
+                    }
+                }
+
+            } else if let Some(name) = segment.strip_prefix(':') {
+                encode(lookup(name), &mut output);
+
+            } else {
+                output.push_str(segment);
+            }
+        }
+
+        for field in fields {
+            if let RouteField::Nested(path) = field {
+                if path != "/" {
+                    output.push_str(path);
+                }
+            }
+        }
+
+        if output.is_empty() {
+            output.push('/');
+        }
+
+        output
+    }
 }
 
+
 #[deprecated(since = "0.5.1", note = "Use the on_click_go_to_url macro instead")]
 #[inline]
 pub fn on_click_go_to_url<A, B>(new_url: A) -> impl FnOnce(DomBuilder<B>) -> DomBuilder<B>
@@ -96,9 +1875,8 @@ pub fn on_click_go_to_url<A, B>(new_url: A) -> impl FnOnce(DomBuilder<B>) -> Dom
 
     #[inline]
     move |dom| {
-        dom.event_with_options(&EventOptions::preventable(), move |e: events::Click| {
-            e.prevent_default();
-            go_to_url(&new_url);
+        dom.event_with_options(&EventOptions::preventable(), move |e: __internal::LinkClick| {
+            e.go_to_url(&new_url);
         })
     }
 }
@@ -115,7 +1893,7 @@ pub fn link<A, F>(url: A, f: F) -> Dom
     let url = url.into();
 
     html!("a", {
-        .attr("href", &url)
+        .attr("href", &browser_url(&url))
         .apply(on_click_go_to_url(url))
         .apply(f)
     })
@@ -139,20 +1917,58 @@ pub fn link<A, F>(url: A, f: F) -> Dom
 /// })
 /// ```
 ///
-/// Also see the [`link!`](crate::link) macro.
+/// The browser still handles the click if:
+///
+/// * A modifier key is held down (such as ctrl+click or cmd+click to open a new tab).
+/// * It isn't a left click.
+/// * The element has a `target` attribute (such as `target="_blank"`) or a `download` attribute.
+/// * The URL is for a different origin.
+/// * Another event listener called `prevent_default`.
+///
+/// Also see the [`link!`](crate::link) and [`intercept_links!`](crate::intercept_links) macros.
 #[macro_export]
 macro_rules! on_click_go_to_url {
     ($this:ident, $url:expr) => {{
         let url = $url;
 
-        $this.event_with_options(&$crate::EventOptions::preventable(), move |e: $crate::events::Click| {
-            e.prevent_default();
-            $crate::routing::go_to_url(&url);
+        $this.event_with_options(&$crate::EventOptions::preventable(), move |e: $crate::routing::__internal::LinkClick| {
+            e.go_to_url(&url);
         })
     }};
 }
 
 
+// TODO test this
+/// Makes every `<a>` element inside of this element work with routing.
+///
+/// This is useful for links which weren't created with [`link!`](crate::link), such as links
+/// inside of HTML which was rendered from Markdown:
+///
+/// ```rust
+/// html!("main", {
+///     .intercept_links!()
+///     .prop("innerHTML", &html)
+/// })
+/// ```
+///
+/// When a link is clicked, it uses [`go_to_url`](crate::routing::go_to_url) instead of the browser's routing.
+///
+/// It only handles links which are inside of the app (the same origin and the same [`base_path`](crate::routing::RouterConfig::base_path)).
+/// It also has the same behavior as [`on_click_go_to_url!`](crate::on_click_go_to_url) for modifier keys, `target`, and `download`.
+///
+/// Links to a fragment in the current page (such as `#section`) are handled by the browser, so it scrolls to them.
+/// In [`RoutingMode::Hash`](crate::routing::RoutingMode::Hash) fragments which start with `/` are routes, so they are still handled.
+#[macro_export]
+macro_rules! intercept_links {
+    ($this:ident $(,)?) => {
+        // This uses bubbling so that event listeners on the links run first
+        $this.event_with_options(&$crate::EventOptions { bubbles: true, preventable: true }, |e: $crate::routing::__internal::LinkClick| {
+            e.intercept();
+        })
+    };
+}
+
+
 // TODO test this
 /// Creates an `<a>` element which works with routing.
 ///
@@ -171,6 +1987,8 @@ macro_rules! on_click_go_to_url {
 /// })
 /// ```
 ///
+/// To highlight the link when it is the current page, use the [`active_link!`](crate::active_link) macro.
+///
 /// Also see the [`on_click_go_to_url!`] macro.
 #[macro_export]
 macro_rules! link {
@@ -178,9 +1996,453 @@ macro_rules! link {
         let url = $url;
 
         $crate::html!("a", {
-            .attr("href", &url)
+            .attr("href", &$crate::routing::browser_url(&url))
             .apply(move |dom| $crate::on_click_go_to_url!(dom, url))
             $($methods)*
         })
     }};
 }
+
+
+/// Highlights a link when its URL matches the current URL.
+///
+/// When the link is active it adds the class, and it sets `aria-current="page"`:
+///
+/// ```rust
+/// link!("/users", {
+///     .active_link!("/users", &*ACTIVE_CLASS)
+/// })
+/// ```
+///
+/// By default the path must match exactly, but you can use [`LinkMatch`](crate::routing::LinkMatch)
+/// to change that:
+///
+/// ```rust
+/// link!("/users?tab=posts", {
+///     // Active for `/users/5?tab=posts`
+///     .active_link!("/users?tab=posts", &*ACTIVE_CLASS, LinkMatch::prefix().with_query())
+/// })
+/// ```
+#[macro_export]
+macro_rules! active_link {
+    ($this:ident, $url:expr, $class:expr) => {
+        $crate::active_link!($this, $url, $class, $crate::routing::LinkMatch::exact())
+    };
+    ($this:ident, $url:expr, $class:expr, $options:expr) => {{
+        let url = $url;
+        let options = $options;
+
+        $this
+            .class_signal($class, $crate::routing::is_active_signal(&url, options))
+            .attr_signal("aria-current", $crate::routing::__internal::aria_current_signal(&url, options))
+    }};
+}
+
+
+/// Creates an `enum` which implements [`Route`](crate::routing::Route).
+///
+/// Each variant has a `#[route(...)]` attribute with the pattern for that route
+/// (see [`match_route`](crate::routing::match_route) for the pattern syntax).
+/// The fields of the variant are the parameters of the pattern:
+///
+/// ```rust
+/// routes! {
+///     #[derive(Debug, Clone, PartialEq)]
+///     pub enum AppRoute {
+///         #[route("/")]
+///         Home,
+///
+///         #[route("/users/:id")]
+///         User { id: u32 },
+///
+///         #[route("/users/:id/posts/*rest")]
+///         Post { id: u32, rest: String },
+///     }
+/// }
+/// ```
+///
+/// The routes are matched in order, the first route which matches is used.
+///
+/// A variant can also have a `#[nested]` field, which is another route. The nested route
+/// matches the rest of the path after the pattern:
+///
+/// ```rust
+/// routes! {
+///     pub enum UserRoute {
+///         #[route("/")]
+///         Profile,
+///
+///         #[route("/settings")]
+///         Settings,
+///     }
+/// }
+///
+/// routes! {
+///     pub enum AppRoute {
+///         // Matches `/users/5` and `/users/5/settings`
+///         #[route("/users/:id")]
+///         User { id: u32, #[nested] child: UserRoute },
+///     }
+/// }
+/// ```
+///
+/// The fields are parsed with [`FromStr`](std::str::FromStr), and they are converted back into
+/// a URL with [`Display`](std::fmt::Display). If a field fails to parse, then the route doesn't match.
+///
+/// You can then use [`route_signal`](crate::routing::route_signal) to get the current route,
+/// and [`to_url`](crate::routing::Route::to_url) to create links:
+///
+/// ```rust
+/// link!(AppRoute::User { id: 5 }.to_url(), {
+///     .text("User 5")
+/// })
+/// ```
+#[macro_export]
+macro_rules! routes {
+    (
+        $(#[$attr:meta])*
+        $vis:vis enum $name:ident {
+            $(
+                $(#[doc = $doc:literal])*
+                #[route($pattern:literal)]
+                $variant:ident $({ $($(#[$field_attr:ident])? $field:ident: $type:ty),* $(,)? })?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis enum $name {
+            $(
+                $(#[doc = $doc])*
+                $variant $({ $($field: $type),* })?,
+            )*
+        }
+
+        impl $crate::routing::Route for $name {
+            #[allow(unused_variables)]
+            fn match_path(path: &str) -> Option<Self> {
+                $(
+                    let route = $crate::routing::__internal::match_variant($pattern, path, |params, rest| {
+                        Some(Self::$variant $({ $($field: $crate::__internal_route_field!(match [$($field_attr)?] params, rest, $field, $type)?),* })?)
+                    });
+
+                    if route.is_some() {
+                        return route;
+                    }
+                )*
+
+                None
+            }
+
+            fn to_path(&self) -> String {
+                match self {
+                    $(
+                        Self::$variant $({ $($field),* })? => {
+                            $crate::routing::__internal::format_route($pattern, &[$($($crate::__internal_route_field!(to_path [$($field_attr)?] $field)),*)?])
+                        },
+                    )*
+                }
+            }
+
+            #[allow(unused_variables)]
+            fn matched_path(&self) -> String {
+                match self {
+                    $(
+                        Self::$variant $({ $($field),* })? => {
+                            $crate::routing::__internal::format_route($pattern, &[$($($crate::__internal_route_field!(matched_path [$($field_attr)?] $field)),*)?])
+                        },
+                    )*
+                }
+            }
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __internal_route_field {
+    (match [nested] $params:ident, $rest:ident, $field:ident, $type:ty) => {
+        $crate::routing::__internal::match_nested::<$type>($rest)
+    };
+    (match [] $params:ident, $rest:ident, $field:ident, $type:ty) => {
+        $params.parse::<$type>(stringify!($field))
+    };
+
+    (to_path [nested] $field:ident) => {
+        $crate::routing::__internal::RouteField::Nested($crate::routing::Route::to_path($field))
+    };
+    (to_path [] $field:ident) => {
+        $crate::routing::__internal::RouteField::Param(stringify!($field), ::std::string::ToString::to_string($field))
+    };
+
+    (matched_path [nested] $field:ident) => {
+        $crate::routing::__internal::RouteField::Skip
+    };
+    (matched_path [] $field:ident) => {
+        $crate::__internal_route_field!(to_path [] $field)
+    };
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use futures_channel::oneshot;
+    use futures_util::future::FutureExt;
+    use super::{match_route, Route, normalize_base, add_base, strip_base, hash_path, is_route_fragment, is_same_document, link_matches, LinkMatch, get_query_param, set_query_param, is_query_param_changed};
+    use super::{pop_state, PopState, Traversal, Blocker, BlockerCheck, check_blockers, register_blocker, unregister_blocker};
+    use super::{scroll_storage_key, encode_scroll, decode_scroll};
+
+    routes! {
+        #[derive(Debug, PartialEq)]
+        enum UserRoute {
+            #[route("/")]
+            Profile,
+
+            #[route("/settings/:tab")]
+            Settings { tab: String },
+        }
+    }
+
+    routes! {
+        #[derive(Debug, PartialEq)]
+        enum TestRoute {
+            #[route("/")]
+            Home,
+
+            #[route("/users/:id")]
+            User { id: u32 },
+
+            #[route("/users/:id/posts/*rest")]
+            Post { id: u32, rest: String },
+
+            #[route("/search/:query")]
+            Search { query: String },
+
+            #[route("/profile/:id")]
+            Profile { id: u32, #[nested] child: UserRoute },
+        }
+    }
+
+    #[test]
+    fn base_path() {
+        assert_eq!(normalize_base("app/"), "/app");
+        assert_eq!(normalize_base("/"), "");
+
+        assert_eq!(add_base("/app", "/users/5"), "/app/users/5");
+        assert_eq!(add_base("/app", "https://example.com/"), "https://example.com/");
+        assert_eq!(add_base("/app", "//example.com/"), "//example.com/");
+
+        assert_eq!(strip_base("/app", "/app"), Some("/"));
+        assert_eq!(strip_base("/app", "/app/users/5"), Some("/users/5"));
+        assert_eq!(strip_base("/app", "/application"), None);
+
+        assert_eq!(hash_path(""), Some("/".to_string()));
+        assert_eq!(hash_path("#"), Some("/".to_string()));
+        assert_eq!(hash_path("#/users/5?foo"), Some("/users/5?foo".to_string()));
+        assert_eq!(hash_path("#section"), None);
+
+        assert!(is_route_fragment(""));
+        assert!(is_route_fragment("#"));
+        assert!(is_route_fragment("#/users/5"));
+        assert!(!is_route_fragment("#section"));
+
+        assert!(is_same_document("https://example.com/users", "https://example.com/users#section"));
+        assert!(is_same_document("https://example.com/users#a", "https://example.com/users#b"));
+        assert!(!is_same_document("https://example.com/users", "https://example.com/users/5#section"));
+        assert!(!is_same_document("https://example.com/users?page=2#section", "https://example.com/users#section"));
+    }
+
+    #[test]
+    fn active_links() {
+        let current = "https://example.com/users/5/?tab=posts&page=2#top";
+
+        assert!(link_matches("https://example.com/users/5", current, LinkMatch::exact()));
+        assert!(!link_matches("https://example.com/users", current, LinkMatch::exact()));
+        assert!(!link_matches("https://other.com/users/5", current, LinkMatch::exact()));
+
+        assert!(link_matches("https://example.com/users", current, LinkMatch::prefix()));
+        assert!(link_matches("https://example.com/", current, LinkMatch::prefix()));
+        assert!(!link_matches("https://example.com/user", current, LinkMatch::prefix()));
+
+        assert!(link_matches("https://example.com/users/5?page=2", current, LinkMatch::exact().with_query()));
+        assert!(!link_matches("https://example.com/users/5?tab=info", current, LinkMatch::exact().with_query()));
+        assert!(link_matches("https://example.com/users?tab=posts", current, LinkMatch::prefix().with_query()));
+    }
+
+    #[test]
+    fn query_params() {
+        assert_eq!(get_query_param("?page=5&q=a+b%26c", "page"), Some("5".to_string()));
+        assert_eq!(get_query_param("?page=5&q=a+b%26c", "q"), Some("a b&c".to_string()));
+        assert_eq!(get_query_param("?flag", "flag"), Some("".to_string()));
+        assert_eq!(get_query_param("", "page"), None);
+
+        assert_eq!(set_query_param("?q=a+b&page=5", "page", Some("6")), "q=a+b&page=6");
+        assert_eq!(set_query_param("?page=5&page=6&q=x", "page", Some("a&b")), "page=a%26b&q=x");
+        assert_eq!(set_query_param("?q=x&page=5", "page", None), "q=x");
+        assert_eq!(set_query_param("", "page", Some("1")), "page=1");
+
+        assert!(!is_query_param_changed("?page=5", "page", &5, &1));
+        assert!(is_query_param_changed("?page=5", "page", &6, &1));
+        assert!(!is_query_param_changed("", "page", &1, &1));
+        assert!(is_query_param_changed("", "page", &2, &1));
+        assert!(!is_query_param_changed("?page=foo", "page", &1, &1));
+        assert!(is_query_param_changed("?page=1", "page", &2, &1));
+    }
+
+    #[test]
+    fn match_patterns() {
+        let params = match_route("/users/:id/posts/*rest", "/users/5/posts/foo/bar").unwrap();
+        assert_eq!(params.get("id"), Some("5"));
+        assert_eq!(params.get("rest"), Some("foo/bar"));
+
+        assert!(match_route("/users/:id", "/users/5/").is_some());
+        assert!(match_route("/users/:id", "/users/").is_none());
+        assert!(match_route("/users/:id", "/users/5/posts").is_none());
+        assert!(match_route("/users", "/posts").is_none());
+    }
+
+    #[test]
+    fn routes() {
+        assert_eq!(TestRoute::match_path("/"), Some(TestRoute::Home));
+        assert_eq!(TestRoute::match_path("/users/5"), Some(TestRoute::User { id: 5 }));
+        assert_eq!(TestRoute::match_path("/users/foo"), None);
+        assert_eq!(TestRoute::match_path("/users/5/posts/1/2"), Some(TestRoute::Post { id: 5, rest: "1/2".to_string() }));
+        assert_eq!(TestRoute::match_path("/search/a%20b"), Some(TestRoute::Search { query: "a b".to_string() }));
+
+        assert_eq!(TestRoute::Home.to_path(), "/");
+        assert_eq!(TestRoute::User { id: 5 }.to_path(), "/users/5");
+        assert_eq!(TestRoute::Post { id: 5, rest: "1/2".to_string() }.to_path(), "/users/5/posts/1/2");
+        assert_eq!(TestRoute::Search { query: "a b/c".to_string() }.to_path(), "/search/a%20b%2Fc");
+    }
+
+    #[test]
+    fn nested_routes() {
+        assert_eq!(TestRoute::match_path("/profile/5"), Some(TestRoute::Profile { id: 5, child: UserRoute::Profile }));
+        assert_eq!(TestRoute::match_path("/profile/5/"), Some(TestRoute::Profile { id: 5, child: UserRoute::Profile }));
+        assert_eq!(TestRoute::match_path("/profile/5/settings/a%2Fb"), Some(TestRoute::Profile { id: 5, child: UserRoute::Settings { tab: "a/b".to_string() } }));
+        assert_eq!(TestRoute::match_path("/profile/5/foo"), None);
+
+        let route = TestRoute::Profile { id: 5, child: UserRoute::Settings { tab: "a/b".to_string() } };
+        assert_eq!(route.to_path(), "/profile/5/settings/a%2Fb");
+        assert_eq!(route.matched_path(), "/profile/5");
+        assert_eq!(TestRoute::Profile { id: 5, child: UserRoute::Profile }.to_path(), "/profile/5");
+    }
+
+    #[test]
+    fn history_traversal() {
+        // Back and forward buttons
+        assert_eq!(pop_state(None, 5, Some(3)), PopState::Check { old_index: 5, new_index: 3 });
+        assert_eq!(pop_state(None, 5, Some(6)), PopState::Check { old_index: 5, new_index: 6 });
+
+        // A new entry which wasn't created by routing
+        assert_eq!(pop_state(None, 5, None), PopState::Check { old_index: 5, new_index: 6 });
+
+        // The same entry
+        assert_eq!(pop_state(None, 5, Some(5)), PopState::Navigate);
+
+        // A blocked navigation was undone
+        assert_eq!(pop_state(Some(Traversal::Undo(5)), 5, Some(5)), PopState::Ignore);
+
+        // A blocked navigation was allowed
+        assert_eq!(pop_state(Some(Traversal::Redo(3)), 5, Some(3)), PopState::Navigate);
+
+        // The user navigated somewhere else before the traversal finished
+        assert_eq!(pop_state(Some(Traversal::Undo(5)), 5, Some(2)), PopState::Check { old_index: 5, new_index: 2 });
+        assert_eq!(pop_state(Some(Traversal::Redo(3)), 5, Some(4)), PopState::Check { old_index: 5, new_index: 4 });
+    }
+
+    #[test]
+    fn navigation_blockers() {
+        let log = Rc::new(RefCell::new(vec![]));
+
+        assert!(matches!(check_blockers(|| unreachable!()), BlockerCheck::Allowed));
+
+        let first = register_blocker(Blocker::Sync(Box::new({
+            let log = log.clone();
+            move |url| {
+                log.borrow_mut().push(format!("sync {}", url));
+                true
+            }
+        })));
+
+        assert!(matches!(check_blockers(|| "/allowed".to_string()), BlockerCheck::Allowed));
+
+        let blocked = register_blocker(Blocker::Sync(Box::new(|_| false)));
+        assert!(matches!(check_blockers(|| "/blocked".to_string()), BlockerCheck::Blocked));
+        unregister_blocker(blocked);
+
+        let receiver = Rc::new(RefCell::new(None));
+
+        let pending = register_blocker(Blocker::Async(Box::new({
+            let log = log.clone();
+            let receiver = receiver.clone();
+            move |url| {
+                log.borrow_mut().push(format!("async {}", url));
+                let receiver: oneshot::Receiver<bool> = receiver.borrow_mut().take().unwrap();
+                Box::pin(receiver.map(|allowed| allowed.unwrap_or(false)))
+            }
+        })));
+
+        let last = register_blocker(Blocker::Sync(Box::new({
+            let log = log.clone();
+            move |url| {
+                log.borrow_mut().push(format!("last {}", url));
+                true
+            }
+        })));
+
+        assert_eq!(*log.borrow(), ["sync /allowed", "sync /blocked"]);
+        log.borrow_mut().clear();
+
+        // The blockers run in the same order that they were created
+        let (sender, rx) = oneshot::channel();
+        *receiver.borrow_mut() = Some(rx);
+
+        match check_blockers(|| "/pending".to_string()) {
+            BlockerCheck::Pending(mut allowed) => {
+                assert_eq!(allowed.as_mut().now_or_never(), None);
+                assert_eq!(*log.borrow(), ["sync /pending", "async /pending"]);
+
+                sender.send(true).unwrap();
+                assert_eq!(allowed.now_or_never(), Some(true));
+                assert_eq!(*log.borrow(), ["sync /pending", "async /pending", "last /pending"]);
+            },
+            _ => panic!("expected Pending"),
+        }
+
+        log.borrow_mut().clear();
+
+        // When the async blocker blocks, the later blockers don't run
+        let (sender, rx) = oneshot::channel();
+        *receiver.borrow_mut() = Some(rx);
+
+        match check_blockers(|| "/cancelled".to_string()) {
+            BlockerCheck::Pending(allowed) => {
+                sender.send(false).unwrap();
+                assert_eq!(allowed.now_or_never(), Some(false));
+                assert_eq!(*log.borrow(), ["sync /cancelled", "async /cancelled"]);
+            },
+            _ => panic!("expected Pending"),
+        }
+
+        unregister_blocker(first);
+        unregister_blocker(pending);
+        unregister_blocker(last);
+
+        assert!(matches!(check_blockers(|| unreachable!()), BlockerCheck::Allowed));
+    }
+
+    #[test]
+    fn scroll_positions() {
+        assert_eq!(scroll_storage_key(1700000000000), "dominator-scroll:1700000000000");
+
+        assert_eq!(encode_scroll((0.0, 250.5)), "0,250.5");
+        assert_eq!(decode_scroll("0,250.5"), Some((0.0, 250.5)));
+        assert_eq!(decode_scroll(&encode_scroll((12.0, -3.0))), Some((12.0, -3.0)));
+
+        assert_eq!(decode_scroll(""), None);
+        assert_eq!(decode_scroll("5"), None);
+        assert_eq!(decode_scroll("a,b"), None);
+    }
+}
@@ -0,0 +1,140 @@
+use std::cell::{Cell, RefCell};
+
+
+thread_local! {
+    static IS_EXTRACTING: Cell<bool> = const { Cell::new(false) };
+    static EXTRACTED: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+}
+
+
+/// Whether the static styles should be applied at runtime.
+///
+/// With the `static-css` feature the static styles are loaded from the extracted `.css` file,
+/// so they are only applied while extracting.
+#[inline]
+pub(crate) fn apply_static() -> bool {
+    !cfg!(feature = "static-css") || IS_EXTRACTING.with(|x| x.get())
+}
+
+#[inline]
+pub(crate) fn push(css: String) {
+    if IS_EXTRACTING.with(|x| x.get()) {
+        EXTRACTED.with(|extracted| extracted.borrow_mut().push(css));
+    }
+}
+
+
+// FNV-1a, this is used instead of std's Hasher because it needs to be stable across Rust versions
+fn hash(value: &str) -> u32 {
+    value.bytes().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x01000193)
+    })
+}
+
+/// Creates a class name based on the source location of the macro and its styles.
+///
+/// Unlike the global counter, this doesn't depend on the order that the macros run in,
+/// so it is the same in every build (as long as the source code doesn't change).
+pub(crate) fn make_static_id(name: &str, location: &'static str, styles: &'static str) -> String {
+    format!("{}_{:08x}", name, hash(&format!("{}\n{}", location, styles)))
+}
+
+
+/// Extracts the static CSS for `class!`, `stylesheet!`, `keyframes!`, and `font_face!`.
+///
+/// Normally the styles are inserted into the page at runtime. That delays the first paint,
+/// and it doesn't work with a strict [Content Security Policy](https://developer.mozilla.org/en-US/docs/Web/HTTP/CSP).
+///
+/// With the `static-css` feature, the static styles (`.style`, `.style_important`, `.style_unchecked`,
+/// and `.raw`) are *not* inserted at runtime, instead they must be loaded from a `.css` file.
+///
+/// `extract_static_css` is used to create that `.css` file. It runs the closure, and then
+/// returns the CSS for all of the static rules which were created inside of the closure:
+///
+/// ```rust
+/// let css = extract_static_css(|| {
+///     // Forces the classes to be created
+///     Lazy::force(&MY_CLASS);
+///     Lazy::force(&OTHER_CLASS);
+/// });
+///
+/// // Save the `css` into a `.css` file
+/// ```
+///
+/// The class names are based on the source location and the styles of the macros, so they are
+/// the same in every build. However, that means that you must recreate the `.css` file whenever you
+/// change your styles.
+///
+/// Only the macros where every method uses literals (like `.style("color", "green")`) are static.
+/// If a macro uses any runtime values or signals (like `.style("width", format!(...))` or `.style_signal`),
+/// then it is created at runtime as usual, with a new class name every time that it runs.
+///
+/// This is not a build-time extraction: the closure creates the rules with the browser's CSS parser,
+/// so `extract_static_css` must run inside of a browser (for example in a headless browser as part of
+/// your build). At runtime the global `<style>` element is still created, but it only contains the
+/// rules which are not static.
+///
+/// Signals (like `.style_signal`) are still applied at runtime. And the [`stylesheet_handle!`](crate::stylesheet_handle),
+/// [`class_handle!`](crate::class_handle) and `#![stylesheet = ...]` rules are always inserted at runtime.
+#[cfg(feature = "static-css")]
+pub fn extract_static_css<F>(f: F) -> String where F: FnOnce() {
+    let was_extracting = IS_EXTRACTING.with(|x| x.replace(true));
+
+    f();
+
+    IS_EXTRACTING.with(|x| x.set(was_extracting));
+
+    EXTRACTED.with(|extracted| extracted.borrow_mut().drain(..).collect::<Vec<String>>().join("\n"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{hash, make_static_id};
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(hash(""), 0x811c9dc5);
+        assert_eq!(hash("a"), 0xe40c292c);
+        assert_eq!(hash("foobar"), 0xbf9cf968);
+    }
+
+    #[test]
+    fn static_id_is_stable() {
+        let styles = ".style(\"color\", \"green\")";
+
+        assert_eq!(make_static_id("__class_", "app::styles:10:5", styles), "__class__3ab2fa24");
+
+        // It doesn't depend on how many times the macro runs
+        assert_eq!(make_static_id("__class_", "app::styles:10:5", styles), "__class__3ab2fa24");
+
+        assert_ne!(make_static_id("__class_", "app::styles:10:5", ".style(\"color\", \"blue\")"), "__class__3ab2fa24");
+        assert_ne!(make_static_id("__class_", "app::styles:11:5", styles), "__class__3ab2fa24");
+        assert_eq!(make_static_id("foo", "app::styles:10:5", styles), "foo_3ab2fa24");
+    }
+
+    #[test]
+    fn only_literals_are_static() {
+        // The macro only looks at the tokens, so the runtime values don't need to exist
+        let is_static = [
+            crate::__internal_is_static!(),
+            crate::__internal_is_static!(.style("color", "green") .raw("color: green;")),
+            crate::__internal_is_static!(.style("color", "green") .pseudo!(":hover", { .style("color", "blue") })),
+            crate::__internal_is_static!(
+                .keyframe("from", |frame| frame.style("opacity", "0"))
+                .keyframe("to", |frame| frame.style("opacity", "1"))
+            ),
+        ];
+
+        let is_dynamic = [
+            crate::__internal_is_static!(.style("width", width)),
+            crate::__internal_is_static!(.style("width", format!("{}px", 10))),
+            crate::__internal_is_static!(.style("color", "green") .style_signal("width", always(width))),
+            crate::__internal_is_static!(.pseudo!(":hover", { .style("width", width) })),
+            crate::__internal_is_static!(.keyframe("from", |frame| frame.style("width", width))),
+        ];
+
+        assert_eq!(is_static, [true; 4]);
+        assert_eq!(is_dynamic, [false; 5]);
+    }
+}
@@ -34,6 +34,20 @@ impl<A> RefCounter<A> {
         self.data.borrow()
     }
 
+    /// Gives a reference to the data.
+    ///
+    /// It will be `None` if the data hasn't been initialized yet, or if it's currently being initialized.
+    pub(crate) fn get(&self) -> Option<Ref<'_, A>> {
+        Ref::filter_map(self.data.try_borrow().ok()?, |data| data.as_ref()).ok()
+    }
+
+    /// Gives a mutable reference to the data.
+    ///
+    /// It will be `None` if the data hasn't been initialized yet.
+    pub(crate) fn borrow_mut(&self) -> RefMut<'_, Option<A>> {
+        self.data.borrow_mut()
+    }
+
     /// Decrements the ref count, cleaning up the data if the count is 0
     pub(crate) fn decrement(&self) {
         let counter = self.counter.get().checked_sub(1).unwrap();
@@ -82,6 +96,11 @@ impl<A> MutableListener<A> {
     pub(crate) fn as_mutable(&self) -> &Mutable<A> {
         &self.mutable
     }
+
+    /// Replaces the listener, the old listener is cleaned up.
+    pub(crate) fn set_listener(&mut self, listener: EventListener) {
+        self.listener = DiscardOnDrop::new(listener);
+    }
 }
 
 